@@ -4,7 +4,9 @@ use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use reqwest::StatusCode;
-use tokio::sync::Semaphore;
+use reqwest::multipart::{Form, Part};
+use std::sync::Arc;
+use tokio::sync::{Mutex, OnceCell, Semaphore};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Notebook {
@@ -47,6 +49,10 @@ pub(crate) struct Api {
     base_url: String,
     notebook_home: Option<PathBuf>,
     sem: Semaphore,
+    /// 已上传的本地附件, 避免重复上传, 每个文件单独等待上传结果
+    assets: Mutex<HashMap<PathBuf, Arc<OnceCell<String>>>>,
+    /// 文档标题到文档id的映射, 每个标题只查询一次, 包括本次导入中将要创建的文档
+    doc_ids: Mutex<HashMap<String, Vec<String>>>,
 }
 
 impl Default for Api {
@@ -72,6 +78,8 @@ impl Api {
             base_url: base_url.to_string(),
             notebook_home: None,
             sem: Semaphore::new(500),
+            assets: Mutex::new(HashMap::new()),
+            doc_ids: Mutex::new(HashMap::new()),
         }
     }
 }
@@ -233,10 +241,140 @@ impl Api {
             Err(anyhow!("Error getting file: {}", path))
         }
     }
+
+    /// 使用markdown创建文档, `path`是文档的人类可读路径, 例如: `/foo/bar`, 返回新文档的id
+    ///
+    /// `id`是可选的文档id, 例如: `20240303150400-a1b2c3d`, 为空时由siyuan生成
+    pub(crate) async fn create_doc_with_md(
+        &self,
+        notebook: &str,
+        path: &str,
+        markdown: &str,
+        id: Option<&str>,
+    ) -> Result<String> {
+        let _permit = self.sem.acquire().await?;
+        let client = reqwest::Client::new();
+        let url = format!("{}/api/filetree/createDocWithMd", self.base_url);
+        let mut payload = json!({"notebook": notebook, "path": path, "markdown": markdown});
+        if let Some(id) = id {
+            payload["id"] = json!(id);
+        }
+        let response = client.post(&url).json(&payload).send().await?;
+        let res: ResponseData<Value> = response.json().await?;
+        if res.code != 0 {
+            Err(anyhow!("Error creating doc: {}, msg: {}", path, res.msg))
+        } else {
+            Ok(res.data.as_str().unwrap_or("").to_string())
+        }
+    }
+
+    /// 上传本地文件到`/assets/`, 返回siyuan中的附件路径, 例如: `assets/image-20210719092549-9j5y79r.png`
+    ///
+    /// 返回例子(其中的data部分)：
+    ///
+    /// ```json
+    /// {
+    ///   "errFiles": [],
+    ///   "succMap": {
+    ///     "image.png": "assets/image-20210719092549-9j5y79r.png"
+    ///   }
+    /// }
+    /// ```
+    pub(crate) async fn upload_asset(&self, path: &Path) -> Result<String> {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow!("Invalid asset path: {}", path.display()))?
+            .to_string();
+        let content = tokio::fs::read(path).await?;
+
+        let _permit = self.sem.acquire().await?;
+        let client = reqwest::Client::new();
+        let url = format!("{}/api/asset/upload", self.base_url);
+        let form = Form::new()
+            .text("assetsDirPath", "/assets/")
+            .part("file[]", Part::bytes(content).file_name(name.clone()));
+        let response = client.post(&url).multipart(form).send().await?;
+        let res: ResponseData<Value> = response.json().await?;
+        if res.code != 0 {
+            return Err(anyhow!("Error uploading asset: {}, msg: {}", name, res.msg));
+        }
+        match res.data["succMap"][&name].as_str() {
+            Some(asset) => Ok(asset.to_string()),
+            None => Err(anyhow!("Error uploading asset: {}", name)),
+        }
+    }
+
+    /// 执行sql查询, 例如: `select id from blocks where type='d' and content='foo'`, 返回查询到的行
+    pub(crate) async fn sql(&self, stmt: &str) -> Result<Vec<Value>> {
+        let _permit = self.sem.acquire().await?;
+        let client = reqwest::Client::new();
+        let url = format!("{}/api/query/sql", self.base_url);
+        let payload = json!({"stmt": stmt});
+        let response = client.post(&url).json(&payload).send().await?;
+        let res: ResponseData<Option<Vec<Value>>> = response.json().await?;
+        if res.code != 0 {
+            Err(anyhow!("Error querying sql: {}, msg: {}", stmt, res.msg))
+        } else {
+            Ok(res.data.unwrap_or_default())
+        }
+    }
+
+    /// 设置块属性, 例如: `{"tags": "a,b"}`, 自定义属性需要以`custom-`开头
+    pub(crate) async fn set_block_attrs(
+        &self,
+        idx: &str,
+        attrs: &HashMap<String, String>,
+    ) -> Result<()> {
+        let _permit = self.sem.acquire().await?;
+        let client = reqwest::Client::new();
+        let url = format!("{}/api/attr/setBlockAttrs", self.base_url);
+        let payload = json!({"id": idx, "attrs": attrs});
+        let response = client.post(&url).json(&payload).send().await?;
+        let res: ResponseData<Value> = response.json().await?;
+        if res.code != 0 {
+            Err(anyhow!(
+                "Error setting block attrs: {}, msg: {}",
+                idx,
+                res.msg
+            ))
+        } else {
+            Ok(())
+        }
+    }
 }
 
 /// 拓展API
 impl Api {
+    /// 读取文档的直接子文档, 返回(子文档id, 标题)
+    pub(crate) async fn get_child_docs(&self, idx: &str) -> Result<Vec<(String, String)>> {
+        let notebook_home = self
+            .notebook_home
+            .as_ref()
+            .ok_or_else(|| anyhow!("No notebooks found. Please call `set_notebook_name` first`"))?;
+        // 子文档保存在和文档同名的文件夹中, 例如: `/xxx/20210808180320-abc.sy` => `/xxx/20210808180320-abc/`
+        let path = self.get_filepath_by_id(idx).await?;
+        let dir = notebook_home.join(path.trim_start_matches('/').trim_end_matches(".sy"));
+        let dir = dir.to_string_lossy().to_string();
+        let Ok(files) = self.read_dir(&dir).await else {
+            return Ok(vec![]);
+        };
+
+        let mut docs = Vec::new();
+        for file in files {
+            if file.is_dir || !file.name.ends_with(".sy") {
+                continue;
+            }
+            let data = self.get_file(&format!("{}/{}", dir, file.name)).await?;
+            let data: Value = serde_json::from_str(&data)?;
+            if let Some(id) = data["ID"].as_str() {
+                let title = data["Properties"]["title"].as_str().unwrap_or("");
+                docs.push((id.to_string(), title.to_string()));
+            }
+        }
+        Ok(docs)
+    }
+
     pub(crate) async fn get_notebook_names(&self) -> Result<Vec<String>> {
         let notebooks = self.list_notebooks().await?;
         let names = notebooks
@@ -258,6 +396,15 @@ impl Api {
         Ok(())
     }
 
+    pub(crate) fn get_notebook_id(&self) -> Result<String> {
+        self.notebook_home
+            .as_ref()
+            .and_then(|home| home.file_name())
+            .and_then(|id| id.to_str())
+            .map(String::from)
+            .ok_or_else(|| anyhow!("No notebooks found. Please call `set_notebook_name` first`"))
+    }
+
     pub(crate) async fn get_all_sy_files(&self) -> Result<Vec<String>> {
         if let Some(notebook_home) = &self.notebook_home {
             let notebook_home = notebook_home.to_str().unwrap();
@@ -268,6 +415,51 @@ impl Api {
         }
     }
 
+    /// 上传本地附件, 同一个文件只会上传一次
+    pub(crate) async fn upload_asset_once(&self, path: &Path) -> Result<String> {
+        let cell = {
+            let mut assets = self.assets.lock().await;
+            assets.entry(path.to_path_buf()).or_default().clone()
+        };
+        let asset = cell.get_or_try_init(|| self.upload_asset(path)).await?;
+        Ok(asset.clone())
+    }
+
+    /// 按照标题查找文档id, 找不到或者有多个同名文档时返回`None`
+    pub(crate) async fn doc_id_by_title(&self, title: &str) -> Result<Option<String>> {
+        let cached = self.doc_ids.lock().await.get(title).cloned();
+        let ids = match cached {
+            Some(ids) => ids,
+            None => {
+                let stmt = format!(
+                    "select id from blocks where type='d' and content='{}'",
+                    title.replace('\'', "''")
+                );
+                let ids = self
+                    .sql(&stmt)
+                    .await?
+                    .iter()
+                    .filter_map(|row| row["id"].as_str().map(String::from))
+                    .collect::<Vec<_>>();
+                let mut doc_ids = self.doc_ids.lock().await;
+                doc_ids.entry(title.to_string()).or_insert(ids).clone()
+            }
+        };
+        match ids.as_slice() {
+            [id] => Ok(Some(id.clone())),
+            _ => Ok(None),
+        }
+    }
+
+    /// 记录将要创建的文档, 创建之前就可以把其他文档中的提及转换为块引用
+    pub(crate) async fn add_doc_title(&self, title: &str, idx: &str) {
+        let mut doc_ids = self.doc_ids.lock().await;
+        let ids = doc_ids.entry(title.to_string()).or_default();
+        if !ids.iter().any(|id| id == idx) {
+            ids.push(idx.to_string());
+        }
+    }
+
     pub(crate) async fn read_dir_all(&self, path: &str) -> Result<Vec<String>> {
         let mut sy_files = vec![];
         let mut dirs = vec![path.to_string()];
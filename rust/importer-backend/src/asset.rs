@@ -0,0 +1,127 @@
+use crate::api::Api;
+use anyhow::Result;
+use fancy_regex::Regex;
+use glob::Pattern;
+use percent_encoding::percent_decode_str;
+use std::path::{Path, PathBuf};
+
+/// notion导出的页面和数据库的后缀, 这些链接会作为文档导入, 其他后缀的本地文件都作为附件上传
+const PAGE_EXTENSIONS: [&str; 4] = ["md", "html", "htm", "csv"];
+
+/// 查找markdown中引用的本地附件链接, 例如: `Page%20abc/image.png`
+pub(crate) fn find_local_assets(data: &str) -> Vec<String> {
+    let re = Regex::new(r#"\[[^\]]*]\(([^)\s]+)(?:\s+"[^"]*")?\)"#).unwrap();
+    let mut links = Vec::new();
+    for cap in re.captures_iter(data).flatten() {
+        let link = &cap[1];
+        if link.contains("://")
+            || link.starts_with("assets/")
+            || link.starts_with('#')
+            || link.starts_with("mailto:")
+        {
+            continue;
+        }
+        let path = link.split(['?', '#']).next().unwrap_or(link);
+        let extension = path
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_lowercase())
+            .filter(|ext| !ext.contains('/'));
+        if let Some(extension) = extension {
+            if !PAGE_EXTENSIONS.contains(&extension.as_str()) && !links.iter().any(|l| l == link) {
+                links.push(link.to_string());
+            }
+        }
+    }
+    links
+}
+
+/// 查找附件链接对应的本地文件
+///
+/// notion的链接是相对于页面所在目录的, 找不到时在整个导出目录中查找, 只有唯一匹配的文件时才使用,
+/// 避免用到其他页面的同名附件
+pub(crate) fn resolve_local_asset(
+    link: &str,
+    page_dir: &Path,
+    export_dir: &Path,
+) -> Option<PathBuf> {
+    let link = link.split(['?', '#']).next().unwrap_or(link);
+    let link = percent_decode_str(link).decode_utf8().ok()?;
+    let path = page_dir.join(link.as_ref());
+    if path.is_file() {
+        return Some(path);
+    }
+
+    let pattern = format!(
+        "{}/**/{}",
+        Pattern::escape(export_dir.to_str()?),
+        Pattern::escape(&link)
+    );
+    let mut matches = glob::glob(&pattern)
+        .ok()?
+        .flatten()
+        .filter(|path| path.is_file());
+    match (matches.next(), matches.next()) {
+        (Some(path), None) => Some(path),
+        _ => None,
+    }
+}
+
+/// 上传markdown中引用的本地附件, 并把链接替换为siyuan中的附件路径
+pub(crate) async fn upload_local_assets(
+    data: &str,
+    api: &Api,
+    page_dir: &Path,
+    export_dir: &Path,
+) -> Result<String> {
+    let mut data = data.to_string();
+    for link in find_local_assets(&data) {
+        if let Some(path) = resolve_local_asset(&link, page_dir, export_dir) {
+            let asset = api.upload_asset_once(&path).await?;
+            data = data.replace(&format!("]({}", link), &format!("]({}", asset));
+        }
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_local_assets() {
+        let data = r#"![](Page%20abc/image.PNG)
+[report](Page%20abc/report.pdf "title") [page](Page%20abc/child.md) [files](Page%20abc/files.zip)
+![](assets/image-20210719092549-9j5y79r.png) ![](https://example.com/a.png)
+<audio controls="controls" src="Page%20abc/a.mp3"></audio> [clip](Page%20abc/clip.mp4?v=1)"#;
+        let target = vec![
+            "Page%20abc/image.PNG",
+            "Page%20abc/report.pdf",
+            "Page%20abc/files.zip",
+            "Page%20abc/clip.mp4?v=1",
+        ];
+        assert_eq!(find_local_assets(data), target);
+    }
+
+    #[test]
+    fn test_resolve_local_asset() {
+        let dir = std::env::temp_dir().join("siyuan-notion-importer-asset-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        for page in ["A", "B"] {
+            std::fs::create_dir_all(dir.join(page)).unwrap();
+            std::fs::write(dir.join(page).join("image.png"), page).unwrap();
+        }
+        std::fs::write(dir.join("B").join("only.png"), "B").unwrap();
+        let page_dir = dir.join("A");
+        assert_eq!(
+            resolve_local_asset("image.png", &page_dir, &dir),
+            Some(page_dir.join("image.png"))
+        );
+        // 其他页面中唯一的文件可以使用, 有多个同名文件时不确定是哪一个
+        assert_eq!(
+            resolve_local_asset("only.png", &page_dir, &dir),
+            Some(dir.join("B").join("only.png"))
+        );
+        assert_eq!(resolve_local_asset("image.png", &dir, &dir), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+use crate::markdown::split_blocks;
+use fancy_regex::Regex;
+use percent_encoding::percent_decode_str;
+
+/// 浏览器可以直接播放的音频后缀
+const AUDIO_EXTENSIONS: [&str; 7] = ["mp3", "wav", "m4a", "flac", "aac", "oga", "opus"];
+
+/// 浏览器可以直接播放的视频后缀
+const VIDEO_EXTENSIONS: [&str; 5] = ["mp4", "webm", "mov", "m4v", "ogv"];
+
+/// 按照类型转换只有一个附件链接的段落(notion的文件、音频、视频和pdf块)
+///
+/// 音频和视频转换为siyuan的音频块和视频块, pdf和其他文件保留为附件链接,
+/// siyuan会在内置的pdf阅读器中打开`assets/`下的pdf. 本地附件需要先上传, 只处理`assets/`和远程的链接
+pub(crate) fn update_attachments(data: &str) -> String {
+    let Some((title, href)) = attachment_link(data) else {
+        return data.to_string();
+    };
+    let path = href.split(['?', '#']).next().unwrap_or(&href);
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_lowercase())
+        .unwrap_or_default();
+    // 远程的ogg是视频还是音频无法确定, 保留为链接
+    if AUDIO_EXTENSIONS.contains(&extension.as_str())
+        || (extension == "ogg" && href.starts_with("assets/"))
+    {
+        return format!(r#"<audio controls="controls" src="{}"></audio>"#, href);
+    }
+    if VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+        return format!(r#"<video controls="controls" src="{}"></video>"#, href);
+    }
+    if !href.starts_with("assets/") {
+        return data.to_string();
+    }
+    let title = title.unwrap_or_else(|| asset_name(path));
+    format!("[{}]({})", title, href)
+}
+
+/// 对整个markdown文档的段落做`update_attachments`, 在上传本地附件之后使用
+pub(crate) fn update_document_attachments(data: &str) -> String {
+    split_blocks(data)
+        .into_iter()
+        .map(|(node_type, block)| match node_type {
+            "NodeParagraph" => update_attachments(&block),
+            _ => block,
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// 解析只包含一个附件链接的段落, 返回(链接标题, 链接)
+fn attachment_link(data: &str) -> Option<(Option<String>, String)> {
+    let re = Regex::new(
+        r#"^(?:<?(https?://\S+?)>?|\[([^\]]*)\]\(((?:assets/|https?://)[^\s)]+)(?:\s+"[^"]*")?\))$"#,
+    )
+    .unwrap();
+    let cap = re.captures(data.trim()).ok()??;
+    match (cap.get(1), cap.get(2), cap.get(3)) {
+        (Some(href), _, _) => Some((None, href.as_str().to_string())),
+        (_, title, Some(href)) => {
+            let title = title.map(|t| t.as_str().trim().to_string());
+            Some((title.filter(|t| !t.is_empty()), href.as_str().to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// 附件的原始文件名, 去掉siyuan上传时加上的时间戳和随机后缀, 例如: `assets/report-20240303150400-a1b2c3d.pdf` => `report.pdf`
+fn asset_name(path: &str) -> String {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let name = percent_decode_str(name).decode_utf8_lossy();
+    let re = Regex::new(r"-\d{14}-[0-9a-z]{7}(?=\.[^.]+$|$)").unwrap();
+    re.replace(&name, "").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_attachments() {
+        assert_eq!(
+            update_attachments("[Voice memo](assets/memo-20240303150400-a1b2c3d.M4A)"),
+            r#"<audio controls="controls" src="assets/memo-20240303150400-a1b2c3d.M4A"></audio>"#
+        );
+        assert_eq!(
+            update_attachments("[](assets/clip-20240303150400-a1b2c3d.mov)"),
+            r#"<video controls="controls" src="assets/clip-20240303150400-a1b2c3d.mov"></video>"#
+        );
+        assert_eq!(
+            update_attachments("[](assets/report-20240303150400-a1b2c3d.pdf)"),
+            "[report.pdf](assets/report-20240303150400-a1b2c3d.pdf)"
+        );
+        assert_eq!(
+            update_attachments("[files.zip](assets/files-20240303150400-a1b2c3d.zip)"),
+            "[files.zip](assets/files-20240303150400-a1b2c3d.zip)"
+        );
+        assert_eq!(
+            update_attachments("See [notes](assets/notes.txt)"),
+            "See [notes](assets/notes.txt)"
+        );
+    }
+}
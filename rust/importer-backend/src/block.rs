@@ -1,3 +1,6 @@
+use crate::html::html_to_kramdown;
+use crate::image::update_images;
+use crate::math::{normalize_inline_math, to_math_block};
 use anyhow::Result;
 use fancy_regex::Regex;
 
@@ -10,19 +13,14 @@ pub(crate) fn update_node_paragraph(data: &str) -> String {
         .collect::<Vec<&str>>()
         .join("\n");
 
-    // 替换掉 \\(\\*) 为 \1
-    let data = if data.contains(r"\$") {
-        let re = Regex::new(r"\\(\\*)").unwrap();
-        re.replace_all(&data, r"$1").to_string()
-    } else {
-        data
-    };
-
-    // 优化图片显示正则表达式替换
-    let re = Regex::new(r"(?<!!)(\[.*?]\(.*?\.(?:bmp|jpg|png|tif|gif|pcx|tga|exif|fpx|svg|psd|cdr|pcd|dxf|ufo|eps|ai|raw|WMF|webp|jpeg)\))").unwrap();
-    let data = re.replace_all(&data, r"!$1").to_string();
+    // 只有一个公式的段落转换为公式块
+    if let Some(math) = to_math_block(&data) {
+        return math;
+    }
+    let data = normalize_inline_math(&data);
 
-    data
+    // 优化图片显示
+    update_images(&data)
 }
 
 /// 更新match block
@@ -83,6 +81,217 @@ pub(crate) fn update_node_blockquote(data: &str) -> Result<String> {
     }
 }
 
+/// 更新html block, 把notion的toggle(`<details><summary>`)转换为折叠块, 分栏转换为超级块,
+/// 没有需要转换的内容时返回`None`, 保留原来的块
+pub(crate) fn update_node_html_block(data: &str) -> Result<Option<String>> {
+    // 移除最后一行
+    let data = data
+        .lines()
+        .take(data.lines().count() - 1)
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    let has_toggle = data.contains("<details");
+    let has_columns = data.contains("column-list");
+    if !has_toggle && !has_columns {
+        return Ok(None);
+    }
+    // 分栏和toggle都由`html_to_kramdown`转换, 分栏中的toggle一起转换为折叠块
+    Ok(Some(html_to_kramdown(&data)))
+}
+
+/// 更新code block: 规范化语言标识, 还原被转义的内容
+///
+/// notion的caption在结束的fence之后, siyuan导入时会变成代码块后面的段落, 由`update_code_caption`处理
+pub(crate) fn update_node_code_block(data: &str) -> String {
+    // 移除最后一行
+    let lines = data
+        .lines()
+        .take(data.lines().count() - 1)
+        .collect::<Vec<&str>>();
+
+    let Some(first) = lines.first() else {
+        return String::new();
+    };
+    let fence_len = first.chars().take_while(|c| *c == '`' || *c == '~').count();
+    if fence_len < 3 {
+        return lines.join("\n");
+    }
+    let fence = &first[..fence_len];
+    let language = normalize_code_language(&first[fence_len..]);
+
+    let end = lines
+        .iter()
+        .rposition(|line| line.trim() == fence)
+        .filter(|end| *end > 0)
+        .unwrap_or(lines.len());
+    let code = unescape_html_entities(&lines[1..end].join("\n"));
+    format!("{}{}\n{}\n{}", fence, language, code, fence)
+}
+
+/// 更新代码块后面的段落: 只有一行的段落是notion的caption, 使用较小的灰色文字显示
+pub(crate) fn update_code_caption(data: &str) -> Option<String> {
+    // 移除最后一行
+    let lines = data
+        .lines()
+        .take(data.lines().count() - 1)
+        .collect::<Vec<&str>>();
+    match lines.as_slice() {
+        [caption] if !caption.trim().is_empty() => Some(format!(
+            "{}\n{{: style=\"font-size: 0.875em; color: var(--b3-theme-on-surface-light);\"}}",
+            caption.trim()
+        )),
+        _ => None,
+    }
+}
+
+/// 把notion的代码语言名称转换为siyuan(highlight.js)使用的名称
+pub(crate) fn normalize_code_language(language: &str) -> String {
+    let language = language.trim().to_lowercase();
+    let language = match language.as_str() {
+        "plain text" | "notion formula" => "plaintext",
+        "c++" => "cpp",
+        "c#" => "csharp",
+        "f#" => "fsharp",
+        "objective-c" => "objectivec",
+        "shell" => "bash",
+        "docker" => "dockerfile",
+        "markup" => "xml",
+        "vb.net" => "vbnet",
+        "visual basic" => "vbscript",
+        "llvm ir" => "llvm",
+        "webassembly" => "wasm",
+        "assembly" => "x86asm",
+        "reason" => "reasonml",
+        "sass" => "scss",
+        "flow" => "javascript",
+        "java/c/c++/c#" => "java",
+        language => language,
+    };
+    language.replace(' ', "")
+}
+
+/// 还原html转义字符
+fn unescape_html_entities(data: &str) -> String {
+    data.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// 更新list: 把notion的to-do(`[ ]`/`[x]`文本或`☐`字符)转换为任务列表
+pub(crate) fn update_node_list(data: &str) -> String {
+    // 移除最后一行
+    let data = data
+        .lines()
+        .take(data.lines().count() - 1)
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    // 列表项的格式: `* {: id="xxx"}\[ ] text`, ial是可选的
+    let re = Regex::new(
+        r"(?m)^(\s*(?:[*+-]|\d+[.)])\s+(?:\{:[^}]*\})?)(?:\\?\[([ xX])\\?\](?!\()|([☐☑☒]))[ \t]*",
+    )
+    .unwrap();
+    let data = re
+        .replace_all(&data, |cap: &fancy_regex::Captures| {
+            let checked = match (cap.get(2), cap.get(3)) {
+                (Some(m), _) => m.as_str() != " ",
+                (None, Some(m)) => m.as_str() != "☐",
+                _ => false,
+            };
+            format!("{}[{}] ", &cap[1], if checked { "X" } else { " " })
+        })
+        .to_string();
+    fold_toggle_items(&data)
+}
+
+/// notion markdown导出的toggle是带有子块的无序列表项, 标题之后空一行是段落、代码块等非列表的子块,
+/// 这样的列表项加上`fold="1"`. 缩进过多的子块会变成缩进代码块, 这里移到列表项内容的位置
+fn fold_toggle_items(data: &str) -> String {
+    let marker_re = Regex::new(r"^([ \t]*)([*+-]|\d{1,9}[.)])([ \t]+)(.*)$").unwrap();
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let lines = data.lines().collect::<Vec<_>>();
+    let base = lines
+        .iter()
+        .filter_map(|line| marker_re.captures(line).ok().flatten())
+        .map(|cap| cap[1].len())
+        .min();
+    let Some(base) = base else {
+        return data.to_string();
+    };
+
+    let mut res = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let cap = match marker_re.captures(lines[i]) {
+            Ok(Some(cap)) if cap[1].len() == base => cap,
+            _ => {
+                res.push(lines[i].to_string());
+                i += 1;
+                continue;
+            }
+        };
+        // 列表项的子块到缩进小于内容位置的行为止
+        let content = cap[1].len() + cap[2].len() + cap[3].len();
+        let end = (i + 1..lines.len())
+            .find(|j| !lines[*j].trim().is_empty() && indent(lines[*j]) < content)
+            .unwrap_or(lines.len());
+        let children = &lines[i + 1..end];
+        let min = children
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| indent(line))
+            .min()
+            .unwrap_or(content);
+        let strip = if min >= content + 4 { min } else { content };
+        let body = children
+            .iter()
+            .map(|line| &line[indent(line).min(strip)..])
+            .collect::<Vec<_>>()
+            .join("\n");
+        let body = fold_toggle_items(&body);
+
+        let text = &cap[4];
+        let task = Regex::new(r"^(?:\{:[^}]*\})?\[[ xX]\]").unwrap();
+        let toggle = matches!(&cap[2], "*" | "+" | "-")
+            && !task.is_match(text).unwrap_or(false)
+            && has_block_children(&body);
+        let text = match (toggle, text.strip_prefix("{:")) {
+            (false, _) => text.to_string(),
+            (true, Some(ial)) if !ial.split('}').next().unwrap_or("").contains("fold=") => {
+                format!("{{: fold=\"1\"{}", ial)
+            }
+            (true, Some(_)) => text.to_string(),
+            (true, None) => format!("{{: fold=\"1\"}}{}", text),
+        };
+        res.push(format!("{}{}{}{}", &cap[1], &cap[2], &cap[3], text));
+        res.extend(body.lines().map(|line| {
+            if line.trim().is_empty() {
+                String::new()
+            } else {
+                format!("{}{}", " ".repeat(content), line)
+            }
+        }));
+        i = end;
+    }
+    res.join("\n")
+}
+
+/// 列表项的内容中空行之后是否有列表和ial以外的块
+fn has_block_children(body: &str) -> bool {
+    let marker_re = Regex::new(r"^\s*(?:[*+-]|\d{1,9}[.)])(?:\s|$)").unwrap();
+    body.lines().zip(body.lines().skip(1)).any(|(prev, line)| {
+        prev.trim().is_empty()
+            && !line.trim().is_empty()
+            && !line.trim_start().starts_with("{:")
+            && !marker_re.is_match(line).unwrap_or(false)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -98,6 +307,9 @@ mod tests {
 这是一行其他文本"#;
         let updated = update_node_paragraph(data);
         assert_eq!(updated, target);
+
+        let data = "$$E = mc^2$$\n{: id=\"xxx\" }";
+        assert_eq!(update_node_paragraph(data), "$$\nE = mc^2\n$$");
     }
 
     #[test]
@@ -128,4 +340,95 @@ mod tests {
         let updated_data = update_node_blockquote(data).unwrap();
         assert_eq!(updated_data, target);
     }
+
+    #[test]
+    fn test_update_node_html_block() {
+        let data = r#"<div>
+<details>
+<summary>Toggle title</summary>
+<p>First line<br>second line</p>
+<details><summary><h2>Nested heading</h2></summary><p>Nested body</p></details>
+</details>
+</div>
+{: id="xxx" }"#;
+        let target = r#"* {: fold="1"}Toggle title
+
+  First line
+  second line
+
+  ## Nested heading
+  {: fold="1"}
+
+  Nested body"#;
+        let updated = update_node_html_block(data).unwrap().unwrap();
+        assert_eq!(updated, target);
+
+        // 没有闭合的toggle, 没有toggle时保留原来的块
+        let data = "<details><summary>Open</summary><p>Body</p>\n{: id=\"xxx\" }";
+        let updated = update_node_html_block(data).unwrap().unwrap();
+        assert_eq!(updated, "* {: fold=\"1\"}Open\n\n  Body");
+        let data = r#"<div class="column-list"><div class="column"><details><summary>Left</summary><p>Body</p></details></div><div class="column"><p>Right</p></div></div>
+{: id="xxx" }"#;
+        let target = "{{{col\n* {: fold=\"1\"}Left\n\n  Body\n\nRight\n}}}";
+        assert_eq!(update_node_html_block(data).unwrap().unwrap(), target);
+        let data = "<iframe src=\"https://example.com\"></iframe>\n{: id=\"xxx\" }";
+        assert!(update_node_html_block(data).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_update_node_code_block() {
+        let data = r#"```C++
+if (a &lt; b &amp;&amp; c) {} // <tag>
+```
+{: id="xxx" }"#;
+        let target = r#"```cpp
+if (a < b && c) {} // <tag>
+```"#;
+        let updated = update_node_code_block(data);
+        assert_eq!(updated, target);
+
+        // siyuan把caption转换为代码块后面的段落
+        let data = "Caption of the code\n{: id=\"yyy\" }";
+        let target = "Caption of the code\n{: style=\"font-size: 0.875em; color: var(--b3-theme-on-surface-light);\"}";
+        assert_eq!(update_code_caption(data).unwrap(), target);
+        assert!(update_code_caption("First line\nsecond line\n{: id=\"yyy\" }").is_none());
+    }
+
+    #[test]
+    fn test_update_node_list() {
+        let data = r#"* {: id="a"}\[ ] buy milk
+  {: id="p1"}
+* {: id="b"}☑ call Jane
+  {: id="p2"}
+
+  1. {: id="c"}[x] nested done
+     {: id="p3"}
+  2. {: id="d"}☐ nested todo
+     {: id="p4"}
+* {: id="e"}plain item
+  {: id="p5"}
+* {: id="f"}✅ shipped
+  {: id="p6"}
+{: id="list"}"#;
+        let target = r#"* {: id="a"}[ ] buy milk
+  {: id="p1"}
+* {: id="b"}[X] call Jane
+  {: id="p2"}
+
+  1. {: id="c"}[X] nested done
+     {: id="p3"}
+  2. {: id="d"}[ ] nested todo
+     {: id="p4"}
+* {: id="e"}plain item
+  {: id="p5"}
+* {: id="f"}✅ shipped
+  {: id="p6"}"#;
+        let updated = update_node_list(data);
+        assert_eq!(updated, target);
+
+        // notion markdown导出的toggle
+        let data = "- Toggle\n\n        **bold** child\n- plain\n  - nested\n{: }";
+        let target = "- {: fold=\"1\"}Toggle\n\n  **bold** child\n- plain\n  - nested";
+        assert_eq!(update_node_list(data), target);
+    }
 }
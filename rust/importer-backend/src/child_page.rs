@@ -0,0 +1,130 @@
+use crate::api::Api;
+use crate::config::ChildLinkMode;
+use crate::importer::strip_notion_id;
+use anyhow::Result;
+use fancy_regex::Regex;
+use percent_encoding::percent_decode_str;
+use serde_json::Value;
+
+/// 处理文档末尾指向子文档的链接, siyuan的文档树中已经有子文档了, 返回删除的块id
+///
+/// notion的父页面末尾会列出所有子页面, 这些链接块按照`mode`删除或者转换为块引用
+pub(crate) async fn update_child_page_links(
+    doc: &Value,
+    api: &Api,
+    mode: &ChildLinkMode,
+) -> Result<Vec<String>> {
+    let mut removed = Vec::new();
+    let (Some(idx), Some(children)) = (doc["ID"].as_str(), doc["Children"].as_array()) else {
+        return Ok(removed);
+    };
+    if *mode == ChildLinkMode::Keep || children.is_empty() {
+        return Ok(removed);
+    }
+    let child_docs = api.get_child_docs(idx).await?;
+    if child_docs.is_empty() {
+        return Ok(removed);
+    }
+
+    for block in children.iter().rev() {
+        if !matches!(block["Type"].as_str(), Some("NodeParagraph" | "NodeList")) {
+            break;
+        }
+        let Some(block_idx) = block["ID"].as_str() else {
+            break;
+        };
+        let markdown_data = api.get_block_kramdown(block_idx).await?;
+        let markdown_data = markdown_data
+            .lines()
+            .take(markdown_data.lines().count().saturating_sub(1))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let Some(links) = child_page_links(&markdown_data, &child_docs) else {
+            break;
+        };
+        match mode {
+            ChildLinkMode::Remove => {
+                api.delete_block(block_idx).await?;
+                removed.push(block_idx.to_string());
+            }
+            _ => {
+                let mut markdown_data = markdown_data;
+                for (link, (id, title)) in links {
+                    let anchor = title.replace('"', "&quot;");
+                    markdown_data =
+                        markdown_data.replace(&link, &format!("(({} \"{}\"))", id, anchor));
+                }
+                api.update_block(&markdown_data, block_idx).await?;
+            }
+        }
+    }
+    Ok(removed)
+}
+
+/// 如果块中只有指向子文档的链接或者块引用, 返回每个链接和对应的子文档(id, 标题), 否则返回`None`
+pub(crate) fn child_page_links<'a>(
+    data: &str,
+    children: &'a [(String, String)],
+) -> Option<Vec<(String, &'a (String, String))>> {
+    let link_re = Regex::new(r"(?<!!)\[([^\]]*)\]\(([^)\s]+)\)").unwrap();
+    let ref_re = Regex::new(r#"\(\((\d{14}-[0-9a-z]{7})(?:\s+(?:"[^"]*"|'[^']*'))?\)\)"#).unwrap();
+    let ial_re = Regex::new(r"\{:[^}]*\}").unwrap();
+    let marker_re = Regex::new(r"(?m)^\s*(?:[*+-]|\d+[.)])\s").unwrap();
+
+    let mut links = Vec::new();
+    for cap in link_re.captures_iter(data).flatten() {
+        let (text, target) = (cap[1].trim(), &cap[2]);
+        let target = percent_decode_str(target).decode_utf8_lossy();
+        let stem = target
+            .rsplit('/')
+            .next()
+            .and_then(|name| name.strip_suffix(".md").or(name.strip_suffix(".html")))
+            .map(strip_notion_id);
+        let child = children
+            .iter()
+            .find(|(_, title)| title == text || stem.as_deref() == Some(title.as_str()))?;
+        links.push((cap[0].to_string(), child));
+    }
+    let mut refs = 0;
+    for cap in ref_re.captures_iter(data).flatten() {
+        children.iter().find(|(id, _)| *id == cap[1])?;
+        refs += 1;
+    }
+    if links.is_empty() && refs == 0 {
+        return None;
+    }
+
+    // 去掉链接之后只能剩下列表标记和空白
+    let rest = link_re.replace_all(data, "");
+    let rest = ref_re.replace_all(&rest, "");
+    let rest = ial_re.replace_all(&rest, "");
+    let rest = marker_re.replace_all(&rest, "");
+    if rest
+        .chars()
+        .all(|c| c.is_whitespace() || ",;|·".contains(c))
+    {
+        Some(links)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_child_page_links() {
+        let children = vec![
+            ("20240303150400-a1b2c3d".to_string(), "Child".to_string()),
+            ("20240303150400-e5f6g7h".to_string(), "Other".to_string()),
+        ];
+        let data = "* {: id=\"x\"}[Child](Parent/Child%200123456789abcdef0123456789abcdef.md)\n* {: id=\"y\"}((20240303150400-e5f6g7h \"Other\"))";
+        let links = child_page_links(data, &children).unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].1 .1, "Child");
+
+        assert!(child_page_links("See [Child](Child.md) for details", &children).is_none());
+        assert!(child_page_links("[Unknown](Unknown.md)", &children).is_none());
+    }
+}
@@ -0,0 +1,200 @@
+use crate::api::Api;
+use crate::dedup::block_text;
+use anyhow::Result;
+use fancy_regex::Regex;
+use serde_json::Value;
+
+/// 分隔线前后加上空行, 避免`---`和前面的文本一起被当作setext标题, 代码块中的不处理
+pub(crate) fn separate_thematic_breaks(data: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    // 代码块开始的fence, 结束的fence至少和它一样长
+    let mut fence: Option<&str> = None;
+    for line in data.lines() {
+        let trimmed = line.trim();
+        let run = fence_run(trimmed);
+        match fence {
+            Some(f) if run.is_some_and(|r| r.len() == trimmed.len() && r.starts_with(f)) => {
+                fence = None;
+            }
+            Some(_) => {}
+            None if run.is_some() => fence = run,
+            None if is_thematic_break(line) => {
+                if lines.last().is_some_and(|last| !last.trim().is_empty()) {
+                    lines.push("");
+                }
+                lines.push("---");
+                lines.push("");
+                continue;
+            }
+            None => {}
+        }
+        lines.push(line);
+    }
+    lines.join("\n").trim_end().to_string()
+}
+
+/// 行首的代码块fence(至少3个`` ` ``或者`~`)
+pub(crate) fn fence_run(line: &str) -> Option<&str> {
+    let c = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = line.chars().take_while(|x| *x == c).count();
+    (len >= 3).then(|| &line[..len])
+}
+
+/// siyuan把分隔线和前面的文本解析为setext标题(`Text\n---`), 拆分为段落和分隔线, 不是这种标题时返回`None`
+///
+/// `setext`是块的`HeadingSetext`, kramdown已经是`## Text`时根据它判断
+pub(crate) fn split_setext_heading(data: &str, setext: bool) -> Option<String> {
+    let lines = data
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("{:"))
+        .collect::<Vec<_>>();
+    let (underline, text) = lines.split_last()?;
+    if !text.is_empty() && underline.trim_start().starts_with('-') && is_thematic_break(underline) {
+        return Some(format!("{}\n\n---", text.join("\n")));
+    }
+    let text = lines.first()?.strip_prefix("## ").filter(|_| setext)?;
+    Some(format!("{}\n\n---", text.trim()))
+}
+
+/// 是否是分隔线, 包括被siyuan转义的`\-\-\-`
+pub(crate) fn is_thematic_break(line: &str) -> bool {
+    let re =
+        Regex::new(r"^\s{0,3}(?:(?:\\?-\s*){3,}|(?:\\?\*\s*){3,}|(?:\\?_\s*){3,}|—{2,})$").unwrap();
+    re.is_match(line.trim_end()).unwrap_or(false)
+}
+
+/// 是否是只有空白、`&nbsp;`或者`<br>`的占位段落, 忽略ial
+pub(crate) fn is_spacer(data: &str) -> bool {
+    let re = Regex::new(r"(?i)&nbsp;|<br\s*/?>|\{:[^}]*\}|[\u{200b}\u{a0}]").unwrap();
+    re.replace_all(data, "").trim().is_empty()
+}
+
+/// 去掉引述块开头和结尾的空行, 连续的空行只保留一个
+pub(crate) fn trim_quote_lines(data: &str) -> String {
+    let is_blank = |line: &str| line.trim() == ">";
+    let lines = data.lines().collect::<Vec<_>>();
+    let mut res: Vec<&str> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if is_blank(line) {
+            let at_start = res.iter().all(|l| is_blank(l));
+            let at_end = lines[i..]
+                .iter()
+                .all(|l| is_blank(l) || !l.starts_with('>'));
+            let repeated = res.last().is_some_and(|l| is_blank(l));
+            if at_start || at_end || repeated {
+                continue;
+            }
+        }
+        res.push(line);
+    }
+    res.join("\n")
+}
+
+/// 删除引述块开头、结尾和连续的空段落(`trim_quote_lines`在siyuan中对应的操作), 返回删除的块id
+///
+/// 只有空段落的引述块不处理
+pub(crate) async fn remove_blank_quote_lines(data: &Value, api: &Api) -> Result<Vec<String>> {
+    let mut removed = Vec::new();
+    let Some(children) = data["Children"].as_array() else {
+        return Ok(removed);
+    };
+    let children = children
+        .iter()
+        .filter(|child| child["Type"] != "NodeBlockquoteMarker")
+        .collect::<Vec<_>>();
+    let empty = children
+        .iter()
+        .map(|child| is_empty_paragraph(child))
+        .collect::<Vec<_>>();
+    if empty.iter().all(|e| *e) {
+        return Ok(removed);
+    }
+    for (i, child) in children.iter().enumerate() {
+        let at_start = empty[..=i].iter().all(|e| *e);
+        let at_end = empty[i..].iter().all(|e| *e);
+        let repeated = i > 0 && empty[i - 1];
+        if empty[i] && (at_start || at_end || repeated) {
+            if let Some(idx) = child["ID"].as_str() {
+                api.delete_block(idx).await?;
+                removed.push(idx.to_string());
+            }
+        }
+    }
+    Ok(removed)
+}
+
+/// 删除连续的空段落, 每段连续的空段落只保留第一个, 返回删除的块id
+pub(crate) async fn remove_empty_paragraphs(data: &Value, api: &Api) -> Result<Vec<String>> {
+    let mut removed = Vec::new();
+    let Some(children) = data["Children"].as_array() else {
+        return Ok(removed);
+    };
+    let mut previous_empty = false;
+    for child in children {
+        let empty = is_empty_paragraph(child);
+        if empty && previous_empty {
+            if let Some(idx) = child["ID"].as_str() {
+                api.delete_block(idx).await?;
+                removed.push(idx.to_string());
+            }
+        }
+        previous_empty = empty;
+    }
+    Ok(removed)
+}
+
+/// 只有文本并且是占位内容的段落
+fn is_empty_paragraph(node: &Value) -> bool {
+    node["Type"] == "NodeParagraph"
+        && node["Children"]
+            .as_array()
+            .is_none_or(|c| c.iter().all(|c| c["Type"] == "NodeText"))
+        && is_spacer(&block_text(node))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_separate_thematic_breaks() {
+        let data = "Some text\n---\nMore\n\n```\n---\n```";
+        assert_eq!(
+            separate_thematic_breaks(data),
+            "Some text\n\n---\n\nMore\n\n```\n---\n```"
+        );
+        // 更长的fence中的```不结束代码块
+        let data = "````\n```\n---\n````";
+        assert_eq!(separate_thematic_breaks(data), data);
+        assert!(is_thematic_break(r"\-\-\-"));
+        assert!(!is_thematic_break("| --- |"));
+    }
+
+    #[test]
+    fn test_split_setext_heading() {
+        let data = "Some text\n---\n{: id=\"x\"}";
+        assert_eq!(
+            split_setext_heading(data, false).unwrap(),
+            "Some text\n\n---"
+        );
+        let data = "## Some text\n{: id=\"x\"}";
+        assert_eq!(
+            split_setext_heading(data, true).unwrap(),
+            "Some text\n\n---"
+        );
+        assert!(split_setext_heading(data, false).is_none());
+    }
+
+    #[test]
+    fn test_trim_quote_lines() {
+        let data = ">\n> first\n>\n>\n> second\n>\n{: id=\"x\"}";
+        assert_eq!(trim_quote_lines(data), "> first\n>\n> second\n{: id=\"x\"}");
+    }
+
+    #[test]
+    fn test_is_spacer() {
+        assert!(is_spacer("&nbsp;\n{: id=\"x\"}"));
+        assert!(is_spacer("<br>\u{200b}"));
+        assert!(!is_spacer("text"));
+    }
+}
@@ -0,0 +1,117 @@
+use fancy_regex::{Captures, Regex};
+use std::collections::HashMap;
+
+/// 把notion的文字颜色和背景颜色转换为siyuan的行内样式
+///
+/// 支持notion html导出的`<span class="highlight-red">`/`<mark class="highlight-yellow_background">`,
+/// 以及块的ial`{: class="block-color-gray_background"}`, `colors`中配置的颜色优先于notion的默认颜色
+pub(crate) fn update_notion_colors(data: &str, colors: &HashMap<String, String>) -> String {
+    let block_re = Regex::new(r#"\n?\{:\s*class="block-color-([a-z_]+)"\s*\}"#).unwrap();
+
+    let data = update_inline_colors(data, colors);
+    let data = block_re.replace_all(&data, |cap: &Captures| {
+        match notion_color_style(&cap[1], colors) {
+            Some(style) => format!("\n{{: style=\"{}\"}}", style),
+            None => String::new(),
+        }
+    });
+    data.to_string()
+}
+
+/// 转换行内的颜色标签, 按照标签的嵌套匹配结束标签, 支持嵌套的`<span>`
+fn update_inline_colors(data: &str, colors: &HashMap<String, String>) -> String {
+    let tag_re = Regex::new(r#"<(/?)(span|mark)\b([^>]*)>"#).unwrap();
+    let class_re = Regex::new(r#"^\s+class="highlight-([a-z_]+)"\s*$"#).unwrap();
+    // 每个未结束的标签对应的结束标签
+    let mut stack: Vec<String> = Vec::new();
+    tag_re
+        .replace_all(data, |cap: &Captures| {
+            if !cap[1].is_empty() {
+                return stack.pop().unwrap_or_else(|| cap[0].to_string());
+            }
+            let color = class_re.captures(&cap[3]).ok().flatten();
+            let Some(color) = color else {
+                stack.push(format!("</{}>", &cap[2]));
+                return cap[0].to_string();
+            };
+            match notion_color_style(&color[1], colors) {
+                Some(style) => {
+                    stack.push("</span>".to_string());
+                    format!(r#"<span data-type="text" style="{}">"#, style)
+                }
+                None => {
+                    stack.push(String::new());
+                    String::new()
+                }
+            }
+        })
+        .to_string()
+}
+
+/// 把notion的颜色名称(例如: `red`, `yellow_background`)转换为css样式, `default`没有样式
+pub(crate) fn notion_color_style(color: &str, colors: &HashMap<String, String>) -> Option<String> {
+    let value = match colors.get(color) {
+        Some(value) => value.as_str(),
+        None => match color.strip_suffix("_background") {
+            Some(color) => notion_background_color(color)?,
+            None => notion_text_color(color)?,
+        },
+    };
+    if color.ends_with("_background") {
+        Some(format!("background-color: {};", value))
+    } else {
+        Some(format!("color: {};", value))
+    }
+}
+
+/// notion默认的文字颜色
+fn notion_text_color(color: &str) -> Option<&'static str> {
+    match color {
+        "gray" => Some("#787774"),
+        "brown" => Some("#9f6b53"),
+        "orange" => Some("#d9730d"),
+        "yellow" => Some("#cb912f"),
+        "teal" | "green" => Some("#448361"),
+        "blue" => Some("#337ea9"),
+        "purple" => Some("#9065b0"),
+        "pink" => Some("#c14c8a"),
+        "red" => Some("#d44c47"),
+        _ => None,
+    }
+}
+
+/// notion默认的背景颜色
+fn notion_background_color(color: &str) -> Option<&'static str> {
+    match color {
+        "gray" => Some("#f1f1ef"),
+        "brown" => Some("#f4eeee"),
+        "orange" => Some("#fbecdd"),
+        "yellow" => Some("#fbf3db"),
+        "teal" | "green" => Some("#edf3ec"),
+        "blue" => Some("#e7f3f8"),
+        "purple" => Some("#f6f3f9"),
+        "pink" => Some("#faf1f5"),
+        "red" => Some("#fdebec"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_notion_colors() {
+        let data = r#"Some <span class="highlight-red">red</span> and <mark class="highlight-yellow_background">marked</mark> <span class="highlight-default">plain</span>
+{: class="block-color-blue_background"}"#;
+        let mut colors = HashMap::new();
+        colors.insert("red".to_string(), "var(--b3-font-color1)".to_string());
+        let target = r#"Some <span data-type="text" style="color: var(--b3-font-color1);">red</span> and <span data-type="text" style="background-color: #fbf3db;">marked</span> plain
+{: style="background-color: #e7f3f8;"}"#;
+        assert_eq!(update_notion_colors(data, &colors), target);
+        let data =
+            r#"<span class="highlight-red">a <span class="highlight-default">b</span> c</span>"#;
+        let target = r#"<span data-type="text" style="color: var(--b3-font-color1);">a b c</span>"#;
+        assert_eq!(update_notion_colors(data, &colors), target);
+    }
+}
@@ -0,0 +1,117 @@
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// 更新notebook时的配置
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// notion导出文件(解压后)的目录, 用于查找本地引用的附件
+    pub export_dir: Option<PathBuf>,
+    /// notion颜色名称到css颜色的映射, 例如: `red` => `var(--b3-font-color1)`, `yellow_background` => `#fbf3db`,
+    /// 没有配置的颜色使用notion的默认颜色
+    pub colors: HashMap<String, String>,
+    /// 允许嵌入为iframe的网站域名(包含子域名), 其他网站的链接作为书签保留
+    pub embed_providers: Vec<String>,
+    /// 不同文档中至少有多少个相同的块才替换为嵌入块(notion的同步块), 默认`None`不去重,
+    /// 相同的内容也可能是有意复制的, 需要时再开启, 例如: `Some(2)`
+    pub min_duplicate_blocks: Option<usize>,
+    /// notion导出的日期, 相对的日期提及(`@Today`)以这一天为准, 导入zip时默认使用zip中文件的修改时间,
+    /// 其他情况默认使用当前日期
+    pub export_date: Option<NaiveDate>,
+    /// 日期提及是否使用`custom-date`行内元素, 否则只保留iso日期
+    pub date_mention_mark: bool,
+    /// 用户提及的格式, `{name}`会被替换为用户名, 例如: `#{name}#`转换为标签, `{name}`转换为文本
+    pub user_mention_format: String,
+    /// 作为siyuan标签的notion属性名称
+    pub tag_property: String,
+    /// 是否把属性中的标签替换为行内标签`#tag#`
+    pub inline_tags: bool,
+    /// notion中创建时间和最后编辑时间的属性名称
+    pub created_property: String,
+    pub updated_property: String,
+    /// 创建文档时是否使用notion的创建时间作为文档id的时间前缀
+    pub timestamp_ids: bool,
+    /// 是否把编号标记`[1]`和文档末尾的参考列表转换为脚注
+    pub footnotes: bool,
+    /// notion目录的处理方式, 默认替换为嵌入当前文档标题的嵌入块
+    pub table_of_contents: NavBlockMode,
+    /// notion面包屑的处理方式, 默认删除
+    pub breadcrumb: NavBlockMode,
+    /// 文档末尾指向子文档的链接的处理方式, 默认删除
+    pub child_page_links: ChildLinkMode,
+    /// 是否删除和文档标题相同的第一个标题
+    pub remove_title_heading: bool,
+    /// 标题级别的调整方式
+    pub heading_levels: HeadingLevelMode,
+    /// 远程图片的缓存目录, 设置后远程图片会下载(已经缓存的直接使用)并上传为siyuan的附件, `None`保留远程链接
+    pub image_cache_dir: Option<PathBuf>,
+}
+
+/// 标题级别的调整方式
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeadingLevelMode {
+    /// 保持不变
+    Keep,
+    /// 所有标题的级别加上指定的值, 例如: `Shift(1)`把一级标题变为二级标题
+    Shift(i32),
+    /// 去掉跳过的级别, 使用的级别从1开始连续
+    Compress,
+}
+
+/// 文档末尾指向子文档的链接的处理方式
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChildLinkMode {
+    /// 保留
+    Keep,
+    /// 删除
+    Remove,
+    /// 转换为块引用
+    Ref,
+}
+
+/// notion目录和面包屑的处理方式
+#[derive(Debug, Clone, PartialEq)]
+pub enum NavBlockMode {
+    /// 保留导出的静态内容
+    Static,
+    /// 替换为自动更新的嵌入块
+    Embed,
+    /// 删除
+    Remove,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let embed_providers = [
+            "youtube.com",
+            "youtu.be",
+            "vimeo.com",
+            "loom.com",
+            "figma.com",
+            "bilibili.com",
+            "codepen.io",
+            "docs.google.com",
+        ];
+        Self {
+            export_dir: None,
+            colors: HashMap::new(),
+            embed_providers: embed_providers.iter().map(|p| p.to_string()).collect(),
+            min_duplicate_blocks: None,
+            export_date: None,
+            date_mention_mark: false,
+            user_mention_format: "@{name}".to_string(),
+            tag_property: "Tags".to_string(),
+            inline_tags: false,
+            created_property: "Created time".to_string(),
+            updated_property: "Last edited time".to_string(),
+            timestamp_ids: false,
+            footnotes: false,
+            table_of_contents: NavBlockMode::Embed,
+            breadcrumb: NavBlockMode::Remove,
+            child_page_links: ChildLinkMode::Remove,
+            remove_title_heading: true,
+            heading_levels: HeadingLevelMode::Keep,
+            image_cache_dir: None,
+        }
+    }
+}
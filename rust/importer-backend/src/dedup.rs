@@ -0,0 +1,221 @@
+use crate::api::Api;
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// 重复区域的内容太短时不替换, 避免把分隔线、空段落之类的块也替换成嵌入块
+const MIN_DUPLICATE_TEXT_LEN: usize = 32;
+
+/// 文档中的一个顶层块
+#[derive(Debug, Clone)]
+pub(crate) struct BlockInfo {
+    pub(crate) id: String,
+    /// 忽略id之后的内容hash
+    pub(crate) hash: u64,
+    /// 包含的叶子块数量
+    pub(crate) weight: usize,
+    /// 包含的文本长度
+    pub(crate) text_len: usize,
+}
+
+/// 一段重复的内容, `copies`会被替换为嵌入`original`的嵌入块
+#[derive(Debug, PartialEq)]
+pub(crate) struct Duplicate {
+    pub(crate) original: Vec<String>,
+    pub(crate) copies: Vec<String>,
+}
+
+/// 把notion的同步块(在每个使用它的页面中都被导出了一份)替换为siyuan的嵌入块, 返回替换的区域数量
+///
+/// `docs`是sy文件的内容, 创建时间最早的文档中的内容作为原始内容保留, 和文件的读取顺序无关
+pub(crate) async fn dedup_documents(docs: &[Value], api: &Api, min_blocks: usize) -> Result<usize> {
+    let mut docs = docs.iter().collect::<Vec<_>>();
+    docs.sort_by_key(|doc| doc_created(doc));
+    let docs = docs.into_iter().map(top_level_blocks).collect::<Vec<_>>();
+    let duplicates = find_duplicates(&docs, min_blocks);
+    for duplicate in &duplicates {
+        let (first, rest) = duplicate.copies.split_first().unwrap();
+        api.update_block(&embed_query(&duplicate.original), first)
+            .await?;
+        for idx in rest {
+            api.delete_block(idx).await?;
+        }
+    }
+    Ok(duplicates.len())
+}
+
+/// 读取文档的顶层块
+pub(crate) fn top_level_blocks(doc: &Value) -> Vec<BlockInfo> {
+    doc["Children"]
+        .as_array()
+        .map(|children| {
+            children
+                .iter()
+                .map(|node| BlockInfo {
+                    id: node["ID"].as_str().unwrap_or("").to_string(),
+                    hash: block_hash(node),
+                    weight: block_weight(node),
+                    text_len: block_text(node).chars().count(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 在不同的文档中查找相同的连续块, 每个区域至少包含`min_blocks`个叶子块
+pub(crate) fn find_duplicates(docs: &[Vec<BlockInfo>], min_blocks: usize) -> Vec<Duplicate> {
+    // 每个hash在之前的文档中第一次出现的位置(文档, 块)
+    let mut first_seen: HashMap<u64, (usize, usize)> = HashMap::new();
+    let mut duplicates = Vec::new();
+    for (doc_idx, doc) in docs.iter().enumerate() {
+        let mut i = 0;
+        while i < doc.len() {
+            let Some(&(orig_doc, orig_start)) = first_seen.get(&doc[i].hash) else {
+                i += 1;
+                continue;
+            };
+            let original = &docs[orig_doc];
+            let mut len = 0;
+            while i + len < doc.len()
+                && orig_start + len < original.len()
+                && doc[i + len].hash == original[orig_start + len].hash
+            {
+                len += 1;
+            }
+            let region = &doc[i..i + len];
+            let weight = region.iter().map(|b| b.weight).sum::<usize>();
+            let text_len = region.iter().map(|b| b.text_len).sum::<usize>();
+            if len > 0 && weight >= min_blocks && text_len >= MIN_DUPLICATE_TEXT_LEN {
+                duplicates.push(Duplicate {
+                    original: original[orig_start..orig_start + len]
+                        .iter()
+                        .map(|b| b.id.clone())
+                        .collect(),
+                    copies: region.iter().map(|b| b.id.clone()).collect(),
+                });
+                i += len;
+            } else {
+                i += 1;
+            }
+        }
+        for (block_idx, block) in doc.iter().enumerate() {
+            first_seen.entry(block.hash).or_insert((doc_idx, block_idx));
+        }
+    }
+    duplicates
+}
+
+/// 文档的创建时间和id, 没有`created`属性时使用id中的时间, 例如: `20240303150400-a1b2c3d`
+fn doc_created(doc: &Value) -> (String, String) {
+    let id = doc["ID"].as_str().unwrap_or("").to_string();
+    let created = doc["Properties"]["created"]
+        .as_str()
+        .map(String::from)
+        .unwrap_or_else(|| id.chars().take(14).collect());
+    (created, id)
+}
+
+/// 嵌入块, 例如: `{{select * from blocks where id='xxx'}}`
+pub(crate) fn embed_query(ids: &[String]) -> String {
+    match ids {
+        [id] => format!("{{{{select * from blocks where id='{}'}}}}", id),
+        _ => {
+            let ids = ids
+                .iter()
+                .map(|id| format!("'{}'", id))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{{{{select * from blocks where id in ({})}}}}", ids)
+        }
+    }
+}
+
+/// 忽略块id和更新时间, 计算块内容的hash
+fn block_hash(node: &Value) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    strip_ids(node).to_string().hash(&mut hasher);
+    hasher.finish()
+}
+
+fn strip_ids(node: &Value) -> Value {
+    match node {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(key, _)| !matches!(key.as_str(), "ID" | "id" | "updated"))
+                .map(|(key, value)| (key.clone(), strip_ids(value)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(strip_ids).collect()),
+        _ => node.clone(),
+    }
+}
+
+fn block_weight(node: &Value) -> usize {
+    match node["Children"].as_array() {
+        Some(children) if children.iter().any(|c| c["Type"] != "NodeText") => children
+            .iter()
+            .filter(|c| c["Type"] != "NodeText")
+            .map(block_weight)
+            .sum::<usize>()
+            .max(1),
+        _ => 1,
+    }
+}
+
+/// 块中的所有文本
+pub(crate) fn block_text(node: &Value) -> String {
+    let mut text = node["Data"].as_str().unwrap_or("").to_string();
+    if let Some(children) = node["Children"].as_array() {
+        for child in children {
+            text.push_str(&block_text(child));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn paragraph(id: &str, text: &str) -> Value {
+        json!({"ID": id, "Type": "NodeParagraph", "Properties": {"id": id}, "Children": [{"Type": "NodeText", "Data": text}]})
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let synced = [
+            "This paragraph is synced between pages",
+            "and so is this one",
+        ];
+        let doc1 = json!({"Children": [
+            paragraph("a1", "Intro"),
+            paragraph("a2", synced[0]),
+            paragraph("a3", synced[1]),
+        ]});
+        let doc2 = json!({"Children": [
+            paragraph("b1", synced[0]),
+            paragraph("b2", synced[1]),
+            paragraph("b3", "Intro"),
+        ]});
+        let docs = [top_level_blocks(&doc1), top_level_blocks(&doc2)];
+        let duplicates = find_duplicates(&docs, 2);
+        assert_eq!(
+            duplicates,
+            vec![Duplicate {
+                original: vec!["a2".to_string(), "a3".to_string()],
+                copies: vec!["b1".to_string(), "b2".to_string()],
+            }]
+        );
+        assert_eq!(
+            embed_query(&duplicates[0].original),
+            "{{select * from blocks where id in ('a2', 'a3')}}"
+        );
+        let older = json!({"ID": "20240101000000-bbbbbbb"});
+        let newer =
+            json!({"ID": "20230101000000-aaaaaaa", "Properties": {"created": "20250101000000"}});
+        assert!(doc_created(&older) < doc_created(&newer));
+    }
+}
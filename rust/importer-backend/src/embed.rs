@@ -0,0 +1,139 @@
+use fancy_regex::Regex;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use url::Url;
+
+/// 把只有一个链接的段落(notion的书签和嵌入)转换为siyuan的iframe/视频块或者带标题的链接
+///
+/// `providers`中的网站(包括其中的pdf)转换为iframe, 视频文件转换为视频块, 其他只有网址的段落转换为带标题的链接.
+/// 远程的ogg可能是音频也可能是视频, 保留为链接
+pub(crate) fn update_embeds(data: &str, providers: &[String]) -> String {
+    let Some((title, href)) = standalone_link(data) else {
+        return data.to_string();
+    };
+    let Ok(url) = Url::parse(&href) else {
+        return data.to_string();
+    };
+    if !matches!(url.scheme(), "http" | "https") {
+        return data.to_string();
+    }
+
+    let path = url.path().to_lowercase();
+    if [".mp4", ".webm", ".ogv", ".mov"]
+        .iter()
+        .any(|ext| path.ends_with(ext))
+    {
+        return format!(r#"<video controls="controls" src="{}"></video>"#, href);
+    }
+    let host = url.host_str().unwrap_or("");
+    let allowed = providers
+        .iter()
+        .any(|p| host == p || host.ends_with(&format!(".{}", p)));
+    if allowed {
+        let src = embed_url(&url);
+        return format!(
+            r#"<iframe src="{0}" data-src="{0}" border="0" frameborder="no" framespacing="0" allowfullscreen="true"></iframe>"#,
+            src
+        );
+    }
+
+    match title {
+        Some(title) if title != href => format!("[{}]({})", title, href),
+        _ => format!("[{}]({})", bookmark_title(&url), href),
+    }
+}
+
+/// 解析只包含一个链接的段落, 返回(链接标题, 网址), 支持`https://..`, `<https://..>`和`[title](https://..)`
+fn standalone_link(data: &str) -> Option<(Option<String>, String)> {
+    let re = Regex::new(r"^(?:<?(https?://\S+?)>?|\[([^\]]*)\]\((https?://[^\s)]+)\))$").unwrap();
+    let cap = re.captures(data.trim()).ok()??;
+    match (cap.get(1), cap.get(2), cap.get(3)) {
+        (Some(href), _, _) => Some((None, href.as_str().to_string())),
+        (_, title, Some(href)) => {
+            let title = title.map(|t| t.as_str().trim().to_string());
+            Some((title.filter(|t| !t.is_empty()), href.as_str().to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// 把网站的分享链接转换为可以嵌入的链接, 例如: `youtube.com/watch?v=id` => `youtube.com/embed/id`
+fn embed_url(url: &Url) -> String {
+    let host = url.host_str().unwrap_or("").trim_start_matches("www.");
+    let segments = url
+        .path_segments()
+        .map(|s| s.filter(|s| !s.is_empty()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let query = |key: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.to_string())
+    };
+    match (host, segments.as_slice()) {
+        ("youtube.com" | "m.youtube.com", ["watch"]) => match query("v") {
+            Some(id) => format!("https://www.youtube.com/embed/{}", id),
+            None => url.to_string(),
+        },
+        ("youtu.be", [id]) => format!("https://www.youtube.com/embed/{}", id),
+        ("vimeo.com", [id]) => format!("https://player.vimeo.com/video/{}", id),
+        ("loom.com", ["share", id]) => format!("https://www.loom.com/embed/{}", id),
+        ("bilibili.com", ["video", id]) => {
+            format!("https://player.bilibili.com/player.html?bvid={}", id)
+        }
+        ("figma.com", [kind, ..]) if *kind != "embed" => format!(
+            "https://www.figma.com/embed?embed_host=share&url={}",
+            utf8_percent_encode(url.as_str(), NON_ALPHANUMERIC)
+        ),
+        _ => url.to_string(),
+    }
+}
+
+/// 没有标题的书签使用网站和路径作为标题, 例如: `https://www.rust-lang.org/learn` => `rust-lang.org/learn`
+fn bookmark_title(url: &Url) -> String {
+    let host = url.host_str().unwrap_or("").trim_start_matches("www.");
+    format!("{}{}", host, url.path().trim_end_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_update_embeds() {
+        let providers = Config::default().embed_providers;
+        let iframe = |src: &str| {
+            format!(
+                r#"<iframe src="{0}" data-src="{0}" border="0" frameborder="no" framespacing="0" allowfullscreen="true"></iframe>"#,
+                src
+            )
+        };
+        assert_eq!(
+            update_embeds("https://www.youtube.com/watch?v=abc123", &providers),
+            iframe("https://www.youtube.com/embed/abc123")
+        );
+        assert_eq!(
+            update_embeds("[Demo](https://www.loom.com/share/xyz)", &providers),
+            iframe("https://www.loom.com/embed/xyz")
+        );
+        assert_eq!(
+            update_embeds("https://example.com/file.pdf", &providers),
+            "[example.com/file.pdf](https://example.com/file.pdf)"
+        );
+        assert_eq!(
+            update_embeds("https://example.com/voice.ogg", &providers),
+            "[example.com/voice.ogg](https://example.com/voice.ogg)"
+        );
+        assert_eq!(
+            update_embeds("https://example.com/clip.MP4", &providers),
+            r#"<video controls="controls" src="https://example.com/clip.MP4"></video>"#
+        );
+        assert_eq!(
+            update_embeds("https://www.rust-lang.org/learn/", &providers),
+            "[rust-lang.org/learn](https://www.rust-lang.org/learn/)"
+        );
+        assert_eq!(
+            update_embeds("See https://example.com", &providers),
+            "See https://example.com"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use fancy_regex::{Captures, Regex};
+use std::collections::HashMap;
+
+/// 把正文中的编号标记(`[1]`)和文档末尾的参考列表转换为siyuan的脚注(`[^1]`和`[^1]: ...`)
+///
+/// 参考列表可以是`[1] ...`, 也可以是标题(例如: `## References`)下面的有序列表`1. ...`,
+/// 参考后面缩进的行属于同一个参考. 代码块和行内代码中的标记不转换,
+/// 只有正文中的所有标记都能在参考列表中找到时才转换
+pub(crate) fn convert_footnotes(data: &str) -> String {
+    let item_re = Regex::new(r"^\s*(?:\[(\d+)\]|(\d+)[.)])\s+(.+)$").unwrap();
+    let heading_re = Regex::new(
+        r"(?i)^#{1,6}\s+(?:references?|footnotes?|notes|sources|参考.*|注释|脚注)\s*:?\s*$",
+    )
+    .unwrap();
+
+    // 从文档末尾向前查找参考列表, `continuation`是当前参考后面缩进的行(倒序)
+    let lines = data.lines().collect::<Vec<_>>();
+    let mut start = lines.len();
+    let mut i = lines.len();
+    let mut continuation: Vec<&str> = Vec::new();
+    let mut definitions = HashMap::new();
+    let mut bracketed = true;
+    while i > 0 {
+        let line = lines[i - 1];
+        i -= 1;
+        if line.trim().is_empty() {
+            if !continuation.is_empty() {
+                continuation.push("");
+            }
+            continue;
+        }
+        let Ok(Some(cap)) = item_re.captures(line) else {
+            if line.starts_with([' ', '\t']) {
+                continuation.push(line);
+                continue;
+            }
+            break;
+        };
+        let number = cap.get(1).or(cap.get(2)).unwrap().as_str().to_string();
+        bracketed &= cap.get(1).is_some();
+        let mut definition = cap[3].trim().to_string();
+        for line in continuation.drain(..).rev() {
+            definition.push('\n');
+            if !line.trim().is_empty() {
+                definition.push_str(&format!("    {}", line.trim()));
+            }
+        }
+        definitions.insert(number, definition.trim_end().to_string());
+        start = i;
+    }
+    if definitions.is_empty() {
+        return data.to_string();
+    }
+    let mut body_end = start;
+    while body_end > 0 && lines[body_end - 1].trim().is_empty() {
+        body_end -= 1;
+    }
+    let has_heading = body_end > 0 && heading_re.is_match(lines[body_end - 1]).unwrap_or(false);
+    // 没有标题的有序列表可能只是普通的列表
+    if !has_heading && !bracketed {
+        return data.to_string();
+    }
+    if has_heading {
+        body_end -= 1;
+    }
+
+    let body = lines[..body_end].join("\n");
+    let mut markers = Vec::new();
+    replace_markers(&body, |number| {
+        markers.push(number.to_string());
+        format!("[{}]", number)
+    });
+    if markers.is_empty() || markers.iter().any(|m| !definitions.contains_key(m)) {
+        return data.to_string();
+    }
+
+    let body = replace_markers(&body, |number| format!("[^{}]", number));
+    let mut numbers = definitions.keys().cloned().collect::<Vec<_>>();
+    numbers.sort_by_key(|n| n.parse::<usize>().unwrap_or(0));
+    let definitions = numbers
+        .iter()
+        .map(|n| format!("[^{}]: {}", n, definitions[n]))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    format!("{}\n\n{}", body.trim_end(), definitions)
+}
+
+/// 替换代码块和行内代码之外的编号标记, `replace`的参数是标记中的数字
+fn replace_markers(data: &str, mut replace: impl FnMut(&str) -> String) -> String {
+    let marker_re = Regex::new(r"(`+).*?\1|(?<![\[!\\])\[(\d+)\](?![(\[:])").unwrap();
+    let fence_re = Regex::new(r"^\s*(`{3,}|~{3,})").unwrap();
+    // 代码块开始的fence, 结束的fence不能比它短
+    let mut fence: Option<String> = None;
+    let mut lines = Vec::new();
+    for line in data.lines() {
+        let run = fence_re
+            .captures(line)
+            .ok()
+            .flatten()
+            .map(|cap| cap[1].to_string());
+        match (&fence, run) {
+            (Some(open), Some(run))
+                if run.starts_with(open.as_str())
+                    && line.trim().chars().all(|c| open.starts_with(c)) =>
+            {
+                fence = None;
+            }
+            (Some(_), _) => {}
+            (None, Some(run)) => fence = Some(run),
+            (None, None) => {
+                let line = marker_re.replace_all(line, |cap: &Captures| match cap.get(2) {
+                    Some(number) => replace(number.as_str()),
+                    None => cap[0].to_string(),
+                });
+                lines.push(line.to_string());
+                continue;
+            }
+        }
+        lines.push(line.to_string());
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_footnotes() {
+        let data = "Rust is fast[1] and safe[2], see [docs](https://doc.rust-lang.org).\n\n## References\n\n1. The benchmarks game\n2. The Rustonomicon";
+        let target = "Rust is fast[^1] and safe[^2], see [docs](https://doc.rust-lang.org).\n\n[^1]: The benchmarks game\n\n[^2]: The Rustonomicon";
+        assert_eq!(convert_footnotes(data), target);
+
+        let data = "Claim[1]\n\n[1] Source";
+        assert_eq!(convert_footnotes(data), "Claim[^1]\n\n[^1]: Source");
+
+        // 没有参考标题的普通有序列表, 或者标记没有对应的参考
+        let data = "Steps[1]\n\n1. First\n2. Second";
+        assert_eq!(convert_footnotes(data), data);
+        let data = "Claim[3]\n\n[1] Source";
+        assert_eq!(convert_footnotes(data), data);
+
+        // 代码中的标记不转换, 参考可以有多行
+        let data = "Use `arr[1]` here[1]\n\n```\nx[2]\n```\n\n[1] First line\n    second line";
+        let target = "Use `arr[1]` here[^1]\n\n```\nx[2]\n```\n\n[^1]: First line\n    second line";
+        assert_eq!(convert_footnotes(data), target);
+    }
+}
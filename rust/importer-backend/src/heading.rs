@@ -0,0 +1,158 @@
+use crate::api::Api;
+use crate::config::{Config, HeadingLevelMode};
+use crate::markdown::split_blocks;
+use anyhow::Result;
+use fancy_regex::Regex;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+
+/// 整理markdown文档的标题: 删除和文档标题相同的第一个标题, 并按照配置调整标题级别
+pub(crate) fn normalize_headings(data: &str, title: Option<&str>, config: &Config) -> String {
+    let mut blocks = split_blocks(data);
+    if let (Some(title), Some(("NodeHeading", first))) = (title, blocks.first()) {
+        if config.remove_title_heading && is_same_title(heading_text(first), title) {
+            blocks.remove(0);
+        }
+    }
+    let levels = blocks
+        .iter()
+        .filter(|(node_type, _)| *node_type == "NodeHeading")
+        .map(|(_, block)| heading_level(block))
+        .collect::<Vec<_>>();
+    let level_map = heading_level_map(&levels, &config.heading_levels);
+    blocks
+        .into_iter()
+        .map(|(node_type, block)| match node_type {
+            "NodeHeading" => {
+                let level = heading_level(&block);
+                set_heading_level(&block, level_map.get(&level).copied().unwrap_or(level))
+            }
+            _ => block,
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// 整理siyuan文档的标题, 返回删除的块id
+pub(crate) async fn update_headings(
+    doc: &Value,
+    api: &Api,
+    config: &Config,
+) -> Result<Vec<String>> {
+    let mut removed = Vec::new();
+    let title = doc["Properties"]["title"].as_str().unwrap_or("");
+    let first = doc["Children"]
+        .as_array()
+        .and_then(|children| children.first())
+        .filter(|child| child["Type"] == "NodeHeading");
+    if let Some(first) = first.filter(|_| config.remove_title_heading) {
+        if let Some(idx) = first["ID"].as_str() {
+            let markdown_data = api.get_block_kramdown(idx).await?;
+            if is_same_title(heading_text(&markdown_data), title) {
+                api.delete_block(idx).await?;
+                removed.push(idx.to_string());
+            }
+        }
+    }
+
+    let mut headings = Vec::new();
+    collect_headings(doc, &mut headings);
+    headings.retain(|(idx, _)| !removed.contains(idx));
+    let levels = headings.iter().map(|(_, level)| *level).collect::<Vec<_>>();
+    let level_map = heading_level_map(&levels, &config.heading_levels);
+    for (idx, level) in headings {
+        match level_map.get(&level) {
+            Some(new_level) if *new_level != level => {
+                let markdown_data = api.get_block_kramdown(&idx).await?;
+                let markdown_data = markdown_data
+                    .lines()
+                    .take(markdown_data.lines().count() - 1)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let markdown_data = set_heading_level(&markdown_data, *new_level);
+                api.update_block(&markdown_data, &idx).await?;
+            }
+            _ => {}
+        }
+    }
+    Ok(removed)
+}
+
+/// 收集文档中所有标题的(id, 级别)
+fn collect_headings(data: &Value, headings: &mut Vec<(String, usize)>) {
+    if data["Type"] == "NodeHeading" {
+        if let (Some(idx), Some(level)) = (data["ID"].as_str(), data["HeadingLevel"].as_u64()) {
+            headings.push((idx.to_string(), level as usize));
+        }
+    }
+    if let Some(children) = data["Children"].as_array() {
+        for child in children {
+            collect_headings(child, headings);
+        }
+    }
+}
+
+/// 计算标题级别的映射
+///
+/// - `Shift(n)`: 所有标题的级别加上`n`, 限制在1到6之间
+/// - `Compress`: 去掉跳过的级别, 使用的级别从1开始连续, 例如: `1, 3, 4` => `1, 2, 3`
+pub(crate) fn heading_level_map(
+    levels: &[usize],
+    mode: &HeadingLevelMode,
+) -> HashMap<usize, usize> {
+    let used = levels.iter().copied().collect::<BTreeSet<_>>();
+    match mode {
+        HeadingLevelMode::Keep => HashMap::new(),
+        HeadingLevelMode::Shift(offset) => used
+            .into_iter()
+            .map(|level| (level, (level as i32 + offset).clamp(1, 6) as usize))
+            .collect(),
+        HeadingLevelMode::Compress => used
+            .into_iter()
+            .enumerate()
+            .map(|(i, level)| (level, i + 1))
+            .collect(),
+    }
+}
+
+fn heading_level(data: &str) -> usize {
+    data.trim_start().chars().take_while(|c| *c == '#').count()
+}
+
+fn heading_text(data: &str) -> &str {
+    let line = data.lines().next().unwrap_or("");
+    line.trim_start().trim_start_matches('#').trim()
+}
+
+fn set_heading_level(data: &str, level: usize) -> String {
+    let re = Regex::new(r"^\s*#{1,6}").unwrap();
+    re.replace(data, "#".repeat(level).as_str()).to_string()
+}
+
+fn is_same_title(heading: &str, title: &str) -> bool {
+    !title.trim().is_empty() && heading.trim().to_lowercase() == title.trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_headings() {
+        let mut config = Config {
+            heading_levels: HeadingLevelMode::Compress,
+            ..Default::default()
+        };
+        let data = "# Page\n\nIntro\n\n# Part\n\n### Detail";
+        assert_eq!(
+            normalize_headings(data, Some("Page"), &config),
+            "Intro\n\n# Part\n\n## Detail"
+        );
+
+        config.heading_levels = HeadingLevelMode::Shift(1);
+        assert_eq!(
+            normalize_headings(data, Some("Other"), &config),
+            "## Page\n\nIntro\n\n## Part\n\n#### Detail"
+        );
+    }
+}
@@ -0,0 +1,625 @@
+use crate::image::{image_markdown, image_width};
+use crate::meta::PageMeta;
+use scraper::{ElementRef, Html, Node, Selector};
+
+/// 把notion导出的html片段转换为siyuan的kramdown
+pub(crate) fn html_to_kramdown(html: &str) -> String {
+    let fragment = Html::parse_fragment(html);
+    convert_blocks(fragment.root_element()).join("\n\n")
+}
+
+/// 把notion导出的html页面转换为siyuan的kramdown, 只转换正文(`<div class="page-body">`)部分
+pub(crate) fn html_page_to_kramdown(html: &str) -> String {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse("div.page-body").unwrap();
+    let body = match document.select(&selector).next() {
+        Some(body) => convert_blocks(body).join("\n\n"),
+        None => convert_blocks(document.root_element()).join("\n\n"),
+    };
+    match convert_properties(&document) {
+        Some(properties) => format!("{}\n\n{}", properties, body),
+        None => body,
+    }
+}
+
+/// 读取html页面头部的图标和封面
+pub(crate) fn html_page_meta(html: &str) -> PageMeta {
+    let document = Html::parse_document(html);
+    let cover = Selector::parse("img.page-cover-image").unwrap();
+    let icon = Selector::parse(".page-header-icon span.icon").unwrap();
+    PageMeta {
+        icon: document
+            .select(&icon)
+            .next()
+            .map(|icon| icon.text().collect::<String>().trim().to_string())
+            .filter(|icon| !icon.is_empty()),
+        cover: document
+            .select(&cover)
+            .next()
+            .and_then(|cover| cover.value().attr("src"))
+            .map(String::from),
+        // 创建时间和最后编辑时间在页面属性中
+        ..Default::default()
+    }
+}
+
+/// 把页面属性(`<table class="properties">`)转换为和markdown导出相同的`名称: 值`
+fn convert_properties(document: &Html) -> Option<String> {
+    let selector = Selector::parse("table.properties tr").unwrap();
+    let lines = document
+        .select(&selector)
+        .filter_map(|row| {
+            let cell = |name: &str| {
+                row.children()
+                    .filter_map(ElementRef::wrap)
+                    .find(|e| e.value().name() == name)
+            };
+            let name = cell("th")?.text().collect::<String>();
+            let value = cell("td")?;
+            // 多选属性的每个选项是一个`selected-value`
+            let options = value
+                .descendants()
+                .filter_map(ElementRef::wrap)
+                .filter(|e| has_class(*e, "selected-value"))
+                .map(|e| e.text().collect::<String>().trim().to_string())
+                .collect::<Vec<_>>();
+            let value = if options.is_empty() {
+                collapse_whitespace(&value.text().collect::<String>())
+            } else {
+                options.join(", ")
+            };
+            Some(format!("{}: {}", name.trim(), value.trim()))
+        })
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+/// 转换元素的所有子节点, 返回kramdown块的列表
+fn convert_blocks(element: ElementRef) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut inline = String::new();
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => inline.push_str(&collapse_whitespace(text)),
+            Node::Element(_) => {
+                let child = ElementRef::wrap(child).unwrap();
+                if is_block_element(child.value().name()) {
+                    push_inline(&mut blocks, &mut inline);
+                    blocks.extend(convert_block(child));
+                } else {
+                    inline.push_str(&convert_inline(child));
+                }
+            }
+            _ => {}
+        }
+    }
+    push_inline(&mut blocks, &mut inline);
+    blocks
+}
+
+/// 把累积的行内内容作为一个段落加入块列表
+fn push_inline(blocks: &mut Vec<String>, inline: &mut String) {
+    let paragraph = inline.trim();
+    if !paragraph.is_empty() {
+        blocks.push(paragraph.to_string());
+    }
+    inline.clear();
+}
+
+/// 转换单个块元素
+fn convert_block(element: ElementRef) -> Vec<String> {
+    let name = element.value().name();
+    match name {
+        "p" => {
+            let paragraph = convert_inline_children(element);
+            if paragraph.trim().is_empty() {
+                vec![]
+            } else {
+                vec![with_block_color(paragraph.trim(), element)]
+            }
+        }
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+            let level = name[1..].parse::<usize>().unwrap_or(1);
+            let title = convert_inline_children(element);
+            let heading = format!("{} {}", "#".repeat(level), title.trim());
+            vec![with_block_color(&heading, element)]
+        }
+        "ul" | "ol" => vec![convert_list(element, name == "ol")],
+        "details" => vec![convert_toggle(element)],
+        "blockquote" => {
+            let quote = convert_blocks(element).join("\n\n");
+            vec![prefix_lines(&quote, "> ", ">")]
+        }
+        "pre" => {
+            let language = element
+                .descendants()
+                .filter_map(ElementRef::wrap)
+                .flat_map(|e| e.value().classes())
+                .find_map(|class| class.strip_prefix("language-"))
+                .unwrap_or("")
+                .to_string();
+            let code = element.text().collect::<String>();
+            vec![format!(
+                "```{}\n{}\n```",
+                language,
+                code.trim_end_matches('\n')
+            )]
+        }
+        "hr" => vec!["---".to_string()],
+        "table" => vec![convert_table(element)],
+        "style" | "script" | "header" => vec![],
+        _ if has_class(element, "table_of_contents") => vec![convert_table_of_contents(element)],
+        _ if has_class(element, "column-list") => vec![convert_column_list(element)],
+        _ if has_class(element, "callout") => vec![convert_callout(element)],
+        _ if has_class(element, "equation") => {
+            vec![format!("$$\n{}\n$$", tex_annotation(element))]
+        }
+        _ if has_class(element, "bookmark") => vec![convert_bookmark(element)],
+        "figure" => convert_figure(element),
+        _ => convert_blocks(element),
+    }
+}
+
+/// 转换列表, 嵌套的列表缩进到列表项下面
+///
+/// notion的to-do列表(`<ul class="to-do-list">`)转换为任务列表, toggle(`<ul class="toggle">`)转换为折叠列表项
+fn convert_list(element: ElementRef, ordered: bool) -> String {
+    let mut items = Vec::new();
+    let start = element
+        .value()
+        .attr("start")
+        .and_then(|start| start.parse::<usize>().ok())
+        .unwrap_or(1);
+    for (i, item) in element
+        .children()
+        .filter_map(ElementRef::wrap)
+        .filter(|child| child.value().name() == "li")
+        .enumerate()
+    {
+        if has_class(element, "toggle") {
+            let toggle = item
+                .children()
+                .filter_map(ElementRef::wrap)
+                .find(|child| child.value().name() == "details");
+            if let Some(toggle) = toggle {
+                items.push(convert_toggle(toggle));
+                continue;
+            }
+        }
+
+        let mut marker = if ordered {
+            format!("{}. ", start + i)
+        } else {
+            "* ".to_string()
+        };
+        let indent = " ".repeat(marker.len());
+        if has_class(element, "to-do-list") {
+            let checked = item
+                .descendants()
+                .filter_map(ElementRef::wrap)
+                .any(|e| has_class(e, "checkbox-on"));
+            marker = format!("{}[{}] ", marker, if checked { "X" } else { " " });
+        }
+        let content = convert_blocks(item).join("\n\n");
+        let (first, rest) = content.split_once('\n').unwrap_or((&content, ""));
+        let mut res = format!("{}{}", marker, first);
+        if !rest.is_empty() {
+            res = format!("{}\n{}", res, prefix_lines(rest, &indent, ""));
+        }
+        items.push(res);
+    }
+    items.join("\n")
+}
+
+/// 把notion的toggle(`<details><summary>`)转换为折叠的列表项, 标题类的toggle转换为折叠标题
+fn convert_toggle(element: ElementRef) -> String {
+    let summary = element
+        .children()
+        .filter_map(ElementRef::wrap)
+        .find(|child| child.value().name() == "summary");
+    let level = summary
+        .and_then(|summary| {
+            summary
+                .children()
+                .filter_map(ElementRef::wrap)
+                .map(|child| child.value().name())
+                .find(|name| matches!(*name, "h1" | "h2" | "h3" | "h4" | "h5" | "h6"))
+        })
+        .and_then(|name| name[1..].parse::<usize>().ok());
+    let title = summary
+        .map(|summary| summary.text().collect::<String>())
+        .unwrap_or_default();
+    let title = collapse_whitespace(title.trim());
+
+    let mut blocks = Vec::new();
+    let mut inline = String::new();
+    for child in element.children().filter_map(ElementRef::wrap) {
+        if Some(child) == summary {
+            continue;
+        }
+        if is_block_element(child.value().name()) {
+            push_inline(&mut blocks, &mut inline);
+            blocks.extend(convert_block(child));
+        } else {
+            inline.push_str(&convert_inline(child));
+        }
+    }
+    push_inline(&mut blocks, &mut inline);
+    let body = blocks.join("\n\n");
+
+    let mut res = match level {
+        Some(level) => format!("{} {}\n{{: fold=\"1\"}}", "#".repeat(level), title),
+        None => format!("* {{: fold=\"1\"}}{}", title),
+    };
+    if !body.is_empty() {
+        let body = match level {
+            Some(_) => body,
+            None => prefix_lines(&body, "  ", ""),
+        };
+        res = format!("{}\n\n{}", res, body);
+    }
+    res
+}
+
+/// 把notion的分栏(`<div class="column-list">`)转换为siyuan的横向超级块
+fn convert_column_list(element: ElementRef) -> String {
+    let columns = element
+        .children()
+        .filter_map(ElementRef::wrap)
+        .filter(|child| has_class(*child, "column"))
+        .map(|column| {
+            let blocks = convert_blocks(column);
+            if blocks.len() == 1 {
+                blocks[0].clone()
+            } else {
+                format!("{{{{{{row\n{}\n}}}}}}", blocks.join("\n\n"))
+            }
+        })
+        .collect::<Vec<_>>();
+    format!("{{{{{{col\n{}\n}}}}}}", columns.join("\n\n"))
+}
+
+/// 把notion的callout转换为引述块, 图标放在第一行的开头
+fn convert_callout(element: ElementRef) -> String {
+    let icon = element
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .find(|e| has_class(*e, "icon"))
+        .map(|icon| icon.text().collect::<String>())
+        .unwrap_or_default();
+    // 第一个div是图标, 最后一个div是内容
+    let content = element
+        .children()
+        .filter_map(ElementRef::wrap)
+        .filter(|child| child.value().name() == "div")
+        .last()
+        .map(|content| convert_blocks(content).join("\n\n"))
+        .unwrap_or_default();
+    let content = if icon.trim().is_empty() {
+        content
+    } else {
+        format!("{} {}", icon.trim(), content)
+    };
+    let quote = prefix_lines(&content, "> ", ">");
+    // 引述块中最后一个段落的ial结束段落, 后面的颜色ial才属于引述块
+    match notion_color(element, "block-color-") {
+        Some(_) => with_block_color(&format!("{}\n> {{: }}", quote), element),
+        None => quote,
+    }
+}
+
+/// 把notion的网页书签转换为带标题的链接
+fn convert_bookmark(element: ElementRef) -> String {
+    let link = if element.value().name() == "a" {
+        Some(element)
+    } else {
+        element
+            .descendants()
+            .filter_map(ElementRef::wrap)
+            .find(|e| e.value().name() == "a")
+    };
+    let href = link.and_then(|a| a.value().attr("href")).unwrap_or("");
+    let title = element
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .find(|e| has_class(*e, "bookmark-title"))
+        .map(|title| title.text().collect::<String>())
+        .filter(|title| !title.trim().is_empty())
+        .unwrap_or_else(|| href.to_string());
+    format!("[{}]({})", title.trim(), href)
+}
+
+/// 转换图片等其他figure, 图片的caption作为alt
+fn convert_figure(element: ElementRef) -> Vec<String> {
+    let caption = element
+        .children()
+        .filter_map(ElementRef::wrap)
+        .find(|child| child.value().name() == "figcaption")
+        .map(convert_inline_children)
+        .unwrap_or_default();
+    let image = element
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .find(|e| e.value().name() == "img");
+    match image {
+        Some(image) => {
+            let alt = if caption.trim().is_empty() {
+                image.value().attr("alt").unwrap_or("")
+            } else {
+                caption.trim()
+            };
+            let src = image.value().attr("src").unwrap_or("");
+            let width = image_width(image.value().attr("style"), image.value().attr("width"));
+            vec![image_markdown(alt, src, width.as_deref())]
+        }
+        None => convert_blocks(element),
+    }
+}
+
+/// 把notion的目录转换为列表, 缩进按照目录项的层级, 由`update_nav_block`决定是否保留
+fn convert_table_of_contents(element: ElementRef) -> String {
+    let list = element
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .filter(|e| has_class(*e, "table_of_contents-item"))
+        .map(|item| {
+            let level = item
+                .value()
+                .classes()
+                .find_map(|class| class.strip_prefix("table_of_contents-indent-"))
+                .and_then(|level| level.parse::<usize>().ok())
+                .unwrap_or(0);
+            let title = item.text().collect::<String>();
+            format!("{}* {}", "  ".repeat(level), title.trim())
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("{}\n{{: custom-notion-block=\"table_of_contents\"}}", list)
+}
+
+/// 转换表格, 第一行作为表头
+fn convert_table(element: ElementRef) -> String {
+    let rows = element
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .filter(|e| e.value().name() == "tr")
+        .enumerate()
+        .map(|(i, row)| {
+            row.children()
+                .filter_map(ElementRef::wrap)
+                .filter(|cell| matches!(cell.value().name(), "td" | "th"))
+                .map(|cell| {
+                    let content = convert_inline_children(cell)
+                        .trim()
+                        .replace('|', "\\|")
+                        .replace('\n', "<br>");
+                    // siyuan没有标题列, 标题列的单元格加粗
+                    if i > 0 && cell.value().name() == "th" {
+                        wrap_inline(&content, "**")
+                    } else {
+                        content
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut lines = Vec::new();
+    for (i, mut row) in rows.into_iter().enumerate() {
+        row.resize(columns, String::new());
+        lines.push(format!("| {} |", row.join(" | ")));
+        if i == 0 {
+            lines.push(format!("|{}", " --- |".repeat(columns)));
+        }
+    }
+    lines.join("\n")
+}
+
+/// 转换元素的所有子节点为行内kramdown
+fn convert_inline_children(element: ElementRef) -> String {
+    element
+        .children()
+        .map(|child| match child.value() {
+            Node::Text(text) => collapse_whitespace(text),
+            Node::Element(_) => convert_inline(ElementRef::wrap(child).unwrap()),
+            _ => String::new(),
+        })
+        .collect()
+}
+
+/// 转换行内元素
+fn convert_inline(element: ElementRef) -> String {
+    if has_class(element, "notion-text-equation-token") {
+        return format!("${}$", tex_annotation(element));
+    }
+    if has_class(element, "bookmark") {
+        return convert_bookmark(element);
+    }
+    let content = convert_inline_children(element);
+    let content = match element.value().name() {
+        "strong" | "b" => wrap_inline(&content, "**"),
+        "em" | "i" => wrap_inline(&content, "*"),
+        "del" | "s" => wrap_inline(&content, "~~"),
+        // 带颜色的高亮由下面的`highlight-*`处理
+        "mark" if notion_color(element, "highlight-").is_none() => wrap_inline(&content, "=="),
+        "code" => wrap_inline(&element.text().collect::<String>(), "`"),
+        "br" => "\n".to_string(),
+        "a" => match element.value().attr("href") {
+            Some(href) => format!("[{}]({})", content.trim(), href),
+            None => content,
+        },
+        "img" => image_markdown(
+            element.value().attr("alt").unwrap_or(""),
+            element.value().attr("src").unwrap_or(""),
+            image_width(element.value().attr("style"), element.value().attr("width")).as_deref(),
+        ),
+        "style" | "script" => String::new(),
+        _ => content,
+    };
+    match notion_color(element, "highlight-") {
+        Some(color) if !content.trim().is_empty() => {
+            format!(r#"<span class="highlight-{}">{}</span>"#, color, content)
+        }
+        _ => content,
+    }
+}
+
+/// 读取katex渲染结果中的tex源码
+fn tex_annotation(element: ElementRef) -> String {
+    let selector = Selector::parse(r#"annotation[encoding="application/x-tex"]"#).unwrap();
+    let tex = match element.select(&selector).next() {
+        Some(annotation) => annotation.text().collect::<String>(),
+        None => element.text().collect::<String>(),
+    };
+    tex.trim().to_string()
+}
+
+/// 保留notion的块颜色(`block-color-*`), 由`update_notion_colors`转换为样式
+fn with_block_color(block: &str, element: ElementRef) -> String {
+    match notion_color(element, "block-color-") {
+        Some(color) => format!("{}\n{{: class=\"block-color-{}\"}}", block, color),
+        None => block.to_string(),
+    }
+}
+
+/// 读取notion的颜色class中的颜色, 例如: `highlight-red` => `red`
+fn notion_color<'a>(element: ElementRef<'a>, prefix: &str) -> Option<&'a str> {
+    element
+        .value()
+        .classes()
+        .find_map(|class| class.strip_prefix(prefix))
+}
+
+/// 用markdown标记包裹行内内容, 首尾的空白放在标记外面
+fn wrap_inline(content: &str, mark: &str) -> String {
+    let trimmed = content.trim();
+    if trimmed.is_empty() {
+        return content.to_string();
+    }
+    let start = &content[..content.len() - content.trim_start().len()];
+    let end = &content[content.trim_end().len()..];
+    format!("{}{}{}{}{}", start, mark, trimmed, mark, end)
+}
+
+/// 会被当作块处理的html元素
+fn is_block_element(name: &str) -> bool {
+    matches!(
+        name,
+        "p" | "h1"
+            | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+            | "ul"
+            | "ol"
+            | "li"
+            | "blockquote"
+            | "pre"
+            | "hr"
+            | "div"
+            | "figure"
+            | "table"
+            | "details"
+            | "article"
+            | "section"
+            | "header"
+            | "nav"
+            | "style"
+            | "script"
+    )
+}
+
+fn has_class(element: ElementRef, class: &str) -> bool {
+    element.value().classes().any(|c| c == class)
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut last_space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !last_space {
+                res.push(' ');
+            }
+            last_space = true;
+        } else {
+            res.push(c);
+            last_space = false;
+        }
+    }
+    res
+}
+
+/// 给每一行加上前缀, 空行使用`empty_prefix`
+pub(crate) fn prefix_lines(data: &str, prefix: &str, empty_prefix: &str) -> String {
+    data.lines()
+        .map(|line| {
+            if line.is_empty() {
+                empty_prefix.to_string()
+            } else {
+                format!("{}{}", prefix, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_to_kramdown_column_list() {
+        let html = r#"<div class="column-list"><div style="width:50%" class="column"><h3>Left</h3><p>Left <strong>body</strong></p></div><div style="width:50%" class="column"><ul class="bulleted-list"><li>Right item</li></ul></div></div>"#;
+        let target = r#"{{{col
+{{{row
+### Left
+
+Left **body**
+}}}
+
+* Right item
+}}}"#;
+        assert_eq!(html_to_kramdown(html), target);
+    }
+
+    #[test]
+    fn test_html_page_to_kramdown() {
+        let html = r##"<html><body><article class="page sans"><header><h1 class="page-title">Title</h1></header><div class="page-body">
+<nav class="block-color-gray table_of_contents"><div class="table_of_contents-item table_of_contents-indent-0"><a class="table_of_contents-link" href="#1">Intro</a></div></nav>
+<figure class="block-color-gray_background callout"><div style="font-size:1.5em"><span class="icon">💡</span></div><div style="width:100%"><p>Remember <mark class="highlight-red">this</mark> and <mark>that</mark></p></div></figure>
+<ul class="toggle"><li><details open=""><summary>More</summary><p>Hidden</p></details></li></ul>
+<ul class="to-do-list"><li><div class="checkbox checkbox-on"></div> <span class="to-do-children-checked">Done</span></li></ul>
+<figure><a href="https://example.com" class="bookmark source"><div class="bookmark-info"><div class="bookmark-text"><div class="bookmark-title">Example</div></div></div></a></figure>
+<p class="block-color-blue">Blue</p>
+</div></article></body></html>"##;
+        let target = r##"* Intro
+{: custom-notion-block="table_of_contents"}
+
+> 💡 Remember <span class="highlight-red">this</span> and ==that==
+> {: }
+{: class="block-color-gray_background"}
+
+* {: fold="1"}More
+
+  Hidden
+
+* [X] Done
+
+[Example](https://example.com)
+
+Blue
+{: class="block-color-blue"}"##;
+        assert_eq!(html_page_to_kramdown(html), target);
+
+        // 分栏中的toggle转换为折叠块
+        let html = r#"<div class="page-body"><div class="column-list"><div class="column"><ul class="toggle"><li><details open=""><summary>Left</summary><p>Hidden</p></details></li></ul></div><div class="column"><p>Right</p></div></div></div>"#;
+        let target = "{{{col\n* {: fold=\"1\"}Left\n\n  Hidden\n\nRight\n}}}";
+        assert_eq!(html_page_to_kramdown(html), target);
+    }
+}
@@ -0,0 +1,346 @@
+use crate::api::Api;
+use anyhow::Result;
+use fancy_regex::{Captures, Regex};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use url::Url;
+
+/// 图片的后缀, 不区分大小写
+const IMAGE_EXTENSIONS: [&str; 15] = [
+    "apng", "avif", "bmp", "gif", "heic", "heif", "ico", "jfif", "jpeg", "jpg", "png", "svg",
+    "tif", "tiff", "webp",
+];
+
+/// 查询参数中表示图片格式的参数, 例如: unsplash的`?fm=jpg`
+const FORMAT_PARAMS: [&str; 3] = ["fm", "format", "ext"];
+
+/// 下载一张图片的超时时间, 超时的图片保留远程链接
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// notion上传文件的域名, 这些链接没有后缀时根据下载的内容判断是否是图片
+const NOTION_FILE_HOSTS: [&str; 3] = ["amazonaws.com", "notion-static.com", "file.notion.so"];
+
+/// 整理段落中的图片
+///
+/// - 指向图片的链接(`[](a.PNG)`, `[](a?fm=avif)`)转换为图片
+/// - html的`<img>`转换为markdown图片, 宽度作为ial的样式保留
+/// - notion的图片说明(alt)作为图片的标题
+pub(crate) fn update_images(data: &str) -> String {
+    let img_re = Regex::new(r"<img\s[^>]*>").unwrap();
+    let data = img_re.replace_all(data, |cap: &Captures| {
+        let attr = |name: &str| html_attr(&cap[0], name);
+        let width = image_width(attr("style").as_deref(), attr("width").as_deref());
+        image_markdown(
+            &attr("alt").unwrap_or_default(),
+            &attr("src").unwrap_or_default(),
+            width.as_deref(),
+        )
+    });
+
+    let link_re = Regex::new(r#"(?<!!)(\[[^\]]*\]\(([^)\s]+)(?:\s+"[^"]*")?\))"#).unwrap();
+    let data = link_re.replace_all(&data, |cap: &Captures| {
+        if is_image_url(&cap[2]) {
+            format!("!{}", &cap[1])
+        } else {
+            cap[0].to_string()
+        }
+    });
+
+    let caption_re = Regex::new(r"!\[([^\]]+)\]\(([^)\s]+)\)").unwrap();
+    caption_re
+        .replace_all(&data, |cap: &Captures| {
+            let (alt, src) = (cap[1].trim(), &cap[2]);
+            if is_caption(alt, src) {
+                format!("![{}]({} \"{}\")", alt, src, alt.replace('"', "&quot;"))
+            } else {
+                cap[0].to_string()
+            }
+        })
+        .to_string()
+}
+
+/// 生成markdown图片, 宽度作为ial的样式, 例如: `![](a.png){: style="width: 240px;"}`
+pub(crate) fn image_markdown(alt: &str, src: &str, width: Option<&str>) -> String {
+    match width {
+        Some(width) => format!("![{}]({}){{: style=\"width: {};\"}}", alt, src, width),
+        None => format!("![{}]({})", alt, src),
+    }
+}
+
+/// 从html的`style`或者`width`属性中读取图片宽度, 只有数字时使用`px`
+pub(crate) fn image_width(style: Option<&str>, width: Option<&str>) -> Option<String> {
+    let style_re = Regex::new(r"(?:^|;)\s*width\s*:\s*([0-9.]+(?:px|%|em|rem)?)").unwrap();
+    let width = style
+        .and_then(|style| style_re.captures(style).ok().flatten())
+        .map(|cap| cap[1].to_string())
+        .or(width.map(|width| width.trim().to_string()))
+        .filter(|width| !width.is_empty())?;
+    if width.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        Some(format!("{}px", width))
+    } else {
+        Some(width)
+    }
+}
+
+/// 根据后缀或者查询参数判断链接是否指向图片
+pub(crate) fn is_image_url(url: &str) -> bool {
+    if url.to_lowercase().starts_with("data:image/") {
+        return true;
+    }
+    let url = url.split('#').next().unwrap_or(url);
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    if has_image_extension(path) {
+        return true;
+    }
+    query
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .any(|(key, value)| {
+            FORMAT_PARAMS.contains(&key.to_lowercase().as_str())
+                && IMAGE_EXTENSIONS.contains(&value.to_lowercase().as_str())
+        })
+}
+
+fn has_image_extension(path: &str) -> bool {
+    path.rsplit_once('.')
+        .map(|(_, ext)| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// alt是否是用户填写的图片说明, notion没有说明时使用文件名或者`Untitled`
+fn is_caption(alt: &str, src: &str) -> bool {
+    !alt.is_empty()
+        && alt != "image"
+        && !alt.starts_with("Untitled")
+        && !has_image_extension(alt)
+        && !src.ends_with(alt)
+}
+
+/// 根据文件头判断图片类型, 返回对应的后缀
+pub(crate) fn sniff_image(bytes: &[u8]) -> Option<&'static str> {
+    let head = |prefix: &[u8]| bytes.starts_with(prefix);
+    if head(b"\x89PNG\r\n\x1a\n") {
+        return Some("png");
+    }
+    if head(b"\xff\xd8\xff") {
+        return Some("jpg");
+    }
+    if head(b"GIF87a") || head(b"GIF89a") {
+        return Some("gif");
+    }
+    if head(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        return Some("webp");
+    }
+    if head(b"II*\0") || head(b"MM\0*") {
+        return Some("tiff");
+    }
+    if head(b"\0\0\x01\0") {
+        return Some("ico");
+    }
+    if head(b"BM") {
+        return Some("bmp");
+    }
+    if bytes.get(4..8) == Some(b"ftyp") {
+        return match bytes.get(8..12)? {
+            b"avif" | b"avis" => Some("avif"),
+            b"heic" | b"heix" | b"heim" | b"heis" | b"mif1" | b"msf1" => Some("heic"),
+            _ => None,
+        };
+    }
+    let text = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    if text.starts_with("<svg") || (text.starts_with("<?xml") && text.contains("<svg")) {
+        return Some("svg");
+    }
+    None
+}
+
+/// 把远程图片保存到缓存目录并上传为siyuan的附件, 已经缓存的图片不再下载, 可以离线导入
+///
+/// 没有后缀的notion文件链接根据下载内容的类型判断是否是图片, 下载失败时保留远程链接
+pub(crate) async fn localize_images(data: &str, api: &Api, cache_dir: &Path) -> Result<String> {
+    let link_re = Regex::new(r"(!?)\[([^\]]*)\]\((https?://[^)\s]+)").unwrap();
+    let mut data = data.to_string();
+    let links = link_re
+        .captures_iter(&data.clone())
+        .flatten()
+        .map(|cap| (cap[1].is_empty(), cap[2].to_string(), cap[3].to_string()))
+        .collect::<Vec<_>>();
+    for (is_link, text, url) in links {
+        if is_link && !is_image_url(&url) && !is_notion_file(&url) {
+            continue;
+        }
+        let path = match cached_image(&url, cache_dir).await {
+            Some(path) => path,
+            None => match download_image(&url, cache_dir).await {
+                Ok(Some(path)) => path,
+                _ => continue,
+            },
+        };
+        let asset = api.upload_asset_once(&path).await?;
+        let (from, to) = if is_link {
+            (
+                format!("[{}]({}", text, url),
+                format!("![{}]({}", text, asset),
+            )
+        } else {
+            (format!("]({}", url), format!("]({}", asset))
+        };
+        data = data.replace(&from, &to);
+    }
+    Ok(data)
+}
+
+fn is_notion_file(url: &str) -> bool {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(String::from))
+        .is_some_and(|host| NOTION_FILE_HOSTS.iter().any(|h| host.ends_with(h)))
+}
+
+/// 缓存文件名由域名和路径组成, notion文件链接的查询参数是会过期的签名, 不包括在文件名中,
+/// 其他链接的查询参数可能指向不同的图片, 使用查询参数的hash区分, 例如: `a.com/img_1f2e3d4c5b6a7980.png`
+fn cache_name(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let mut path = url.path().to_string();
+    if let Some(query) = url.query().filter(|_| !is_notion_file(url.as_str())) {
+        let mut hasher = DefaultHasher::new();
+        query.hash(&mut hasher);
+        let suffix = format!("_{:016x}", hasher.finish());
+        let stem_end = path
+            .rfind('.')
+            .filter(|i| !path[*i..].contains('/'))
+            .unwrap_or(path.len());
+        path.insert_str(stem_end, &suffix);
+    }
+    let name = format!("{}{}", url.host_str()?, path);
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "._-".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    Some(name)
+}
+
+/// 在缓存目录中查找图片, 没有后缀的链接缓存时会加上图片的后缀
+///
+/// 只有确认是图片的内容才会被缓存, 所以不再检查文件内容
+async fn cached_image(url: &str, cache_dir: &Path) -> Option<PathBuf> {
+    let name = cache_name(url)?;
+    let mut candidates = vec![cache_dir.join(&name)];
+    if !has_image_extension(&name) {
+        candidates = IMAGE_EXTENSIONS
+            .iter()
+            .map(|ext| cache_dir.join(format!("{}.{}", name, ext)))
+            .collect();
+    }
+    for path in candidates {
+        if tokio::fs::metadata(&path).await.is_ok_and(|m| m.is_file()) {
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// 下载图片到缓存目录, 不是图片时返回`None`
+async fn download_image(url: &str, cache_dir: &Path) -> Result<Option<PathBuf>> {
+    let Some(name) = cache_name(url) else {
+        return Ok(None);
+    };
+    let client = reqwest::Client::builder()
+        .timeout(DOWNLOAD_TIMEOUT)
+        .build()?;
+    let response = client.get(url).send().await?.error_for_status()?;
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("")
+        .to_lowercase();
+    let bytes = response.bytes().await?;
+    let Some(extension) = sniff_image(&bytes).or_else(|| {
+        let subtype = content_type.strip_prefix("image/")?;
+        let subtype = subtype.split([';', '+']).next()?;
+        IMAGE_EXTENSIONS.iter().copied().find(|ext| *ext == subtype)
+    }) else {
+        return Ok(None);
+    };
+    let path = if has_image_extension(&name) {
+        cache_dir.join(&name)
+    } else {
+        cache_dir.join(format!("{}.{}", name, extension))
+    };
+    tokio::fs::create_dir_all(cache_dir).await?;
+    tokio::fs::write(&path, &bytes).await?;
+    Ok(Some(path))
+}
+
+fn html_attr(tag: &str, name: &str) -> Option<String> {
+    let re = Regex::new(&format!(r#"\s{}\s*=\s*(?:"([^"]*)"|'([^']*)')"#, name)).unwrap();
+    let cap = re.captures(tag).ok()??;
+    cap.get(1).or(cap.get(2)).map(|m| m.as_str().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_images() {
+        let data = "[](a/Photo.PNG) [](https://images.unsplash.com/photo-1?fm=avif&w=800) [site](https://example.com)";
+        assert_eq!(
+            update_images(data),
+            "![](a/Photo.PNG) ![](https://images.unsplash.com/photo-1?fm=avif&w=800) [site](https://example.com)"
+        );
+        assert_eq!(
+            update_images("![A sunset](img.heic) ![Untitled](Untitled.png)"),
+            "![A sunset](img.heic \"A sunset\") ![Untitled](Untitled.png)"
+        );
+        assert_eq!(
+            update_images(r#"<img src="a.png" alt="" style="width:240px">"#),
+            r#"![](a.png){: style="width: 240px;"}"#
+        );
+    }
+
+    #[test]
+    fn test_sniff_image() {
+        assert_eq!(sniff_image(b"\x89PNG\r\n\x1a\n...."), Some("png"));
+        assert_eq!(sniff_image(b"\0\0\0\x1cftypavif"), Some("avif"));
+        assert_eq!(sniff_image(b"<?xml version=\"1.0\"?><svg/>"), Some("svg"));
+        assert_eq!(sniff_image(b"%PDF-1.7"), None);
+    }
+
+    #[test]
+    fn test_cache_name() {
+        let a = cache_name("https://example.com/img.php?id=1").unwrap();
+        let b = cache_name("https://example.com/img.php?id=2").unwrap();
+        assert_ne!(a, b);
+        assert!(a.starts_with("example.com_img_") && a.ends_with(".php"));
+        assert_eq!(
+            cache_name("https://file.notion.so/f/abc.png?sig=1"),
+            cache_name("https://file.notion.so/f/abc.png?sig=2")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cached_image() {
+        let dir = std::env::temp_dir().join("siyuan-notion-importer-image-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        // 按照content-type缓存的图片不需要能被识别
+        std::fs::write(dir.join("file.notion.so_f_abc.avif"), b"not sniffed").unwrap();
+        let path = cached_image("https://file.notion.so/f/abc?sig=1", &dir).await;
+        assert_eq!(path, Some(dir.join("file.notion.so_f_abc.avif")));
+        assert!(cached_image("https://file.notion.so/f/other", &dir)
+            .await
+            .is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
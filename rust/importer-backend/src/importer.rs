@@ -0,0 +1,352 @@
+use crate::api::Api;
+use crate::asset::upload_local_assets;
+use crate::attachment::update_document_attachments;
+use crate::config::Config;
+use crate::heading::normalize_headings;
+use crate::html::{html_page_meta, html_page_to_kramdown};
+use crate::image::localize_images;
+use crate::markdown::transform_markdown;
+use crate::mention::update_page_mentions;
+use crate::meta::{markdown_cover, parse_notion_time, set_page_meta, timestamp_id, PageMeta};
+use crate::tag::{
+    csv_column, csv_tags, inline_property_tags, property_tags, property_value, set_doc_tags,
+};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, NaiveDate};
+use fancy_regex::Regex;
+use glob::Pattern;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+/// notion导出的一个页面, 可能是markdown/html文件, 也可能是数据库的csv文件
+#[derive(Debug)]
+pub(crate) struct Page {
+    /// siyuan中的人类可读路径, 例如: `/Parent/Child`
+    pub(crate) hpath: String,
+    pub(crate) file: PathBuf,
+}
+
+/// 直接导入notion导出的zip文件(markdown/csv导出或者html导出), 返回创建的文档id
+pub(crate) async fn import_zip(path: &Path, api: &Api, config: &Config) -> Result<Vec<String>> {
+    let name = path
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("Invalid zip path: {}", path.display()))?;
+    let dir = std::env::temp_dir().join(format!("siyuan-notion-importer-{}", name));
+    // 清除上次导入留下的文件
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    extract_zip(path, &dir)?;
+    let config = Config {
+        export_date: config.export_date.or_else(|| export_date(path)),
+        ..config.clone()
+    };
+    let res = import_dir(&dir, api, &config).await;
+    let _ = fs::remove_dir_all(&dir);
+    res
+}
+
+/// 解压zip, 大的导出会被notion拆分成多个嵌套的zip(`Export-xxx-Part-1.zip`)
+///
+/// 只有zip根目录中的导出分卷(`Export-*.zip`, `*-Part-N.zip`)会被解压, 页面中的zip附件保持不变.
+/// 每个分卷先解压到单独的目录, 处理完其中嵌套的分卷之后再合并到zip所在的目录
+fn extract_zip(path: &Path, dir: &Path) -> Result<()> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    archive.extract(dir)?;
+
+    let part_re = Regex::new(r"^(?:Export-.*|.*-Part-\d+)\.zip$").unwrap();
+    let mut parts = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_file() && part_re.is_match(&name).unwrap_or(false) {
+            parts.push(entry.path());
+        }
+    }
+    for part in parts {
+        let target = part.with_extension("zip-extracted");
+        extract_zip(&part, &target)?;
+        fs::remove_file(&part)?;
+        merge_dir(&target, dir)?;
+    }
+    Ok(())
+}
+
+/// notion导出的日期, 使用zip中文件最晚的修改时间, 没有时使用zip文件本身的修改时间
+fn export_date(path: &Path) -> Option<NaiveDate> {
+    let mut archive = ZipArchive::new(File::open(path).ok()?).ok()?;
+    let latest = (0..archive.len())
+        .filter_map(|i| {
+            let time = archive.by_index_raw(i).ok()?.last_modified()?;
+            // 没有时间的文件使用dos时间的最小值
+            if time.year() <= 1980 {
+                return None;
+            }
+            NaiveDate::from_ymd_opt(time.year().into(), time.month().into(), time.day().into())
+        })
+        .max();
+    latest.or_else(|| {
+        let modified = fs::metadata(path).ok()?.modified().ok()?;
+        Some(DateTime::<Local>::from(modified).date_naive())
+    })
+}
+
+/// 把`src`目录中的文件移动到`dst`目录, 同名的目录合并
+fn merge_dir(src: &Path, dst: &Path) -> Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() && target.is_dir() {
+            merge_dir(&entry.path(), &target)?;
+        } else {
+            fs::rename(entry.path(), &target)?;
+        }
+    }
+    fs::remove_dir_all(src)?;
+    Ok(())
+}
+
+/// 导入解压后的notion导出目录, 按照目录结构创建文档
+pub(crate) async fn import_dir(dir: &Path, api: &Api, config: &Config) -> Result<Vec<String>> {
+    let notebook = api.get_notebook_id()?;
+    // 所有页面转换之后再创建文档, 页面提及可以引用后面的页面
+    let mut docs = Vec::new();
+    // 数据库中每一行(子页面)的标签, 键是子页面的hpath
+    let mut row_tags = HashMap::new();
+    // 数据库中每一行的创建时间和最后编辑时间
+    let mut row_times = HashMap::new();
+    for page in collect_pages(dir)? {
+        let markdown = read_page(&page.file)?;
+        let (markdown, mut meta) = match page.file.extension().and_then(|ext| ext.to_str()) {
+            Some("html") => (markdown, html_page_meta(&fs::read_to_string(&page.file)?)),
+            Some("md") => {
+                let (cover, markdown) = markdown_cover(&markdown);
+                let meta = PageMeta {
+                    cover,
+                    ..Default::default()
+                };
+                (markdown, meta)
+            }
+            _ => (markdown, PageMeta::default()),
+        };
+        let mut tags = property_tags(&markdown, &config.tag_property);
+        if page.file.extension().is_some_and(|ext| ext == "csv") {
+            let data = fs::read_to_string(&page.file)?;
+            for (name, tags) in csv_tags(data.trim_start_matches('\u{feff}'), &config.tag_property)?
+            {
+                row_tags.insert(format!("{}/{}", page.hpath, name), tags);
+            }
+            let data = data.trim_start_matches('\u{feff}');
+            let created = csv_column(data, &config.created_property)?;
+            let mut updated = csv_column(data, &config.updated_property)?;
+            for (name, created) in created {
+                let updated = updated.remove(&name);
+                row_times.insert(format!("{}/{}", page.hpath, name), (Some(created), updated));
+            }
+            for (name, updated) in updated {
+                row_times.insert(format!("{}/{}", page.hpath, name), (None, Some(updated)));
+            }
+        }
+        for tag in row_tags.remove(&page.hpath).unwrap_or_default() {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        let (row_created, row_updated) = row_times.remove(&page.hpath).unwrap_or_default();
+        meta.created = property_value(&markdown, &config.created_property)
+            .map(String::from)
+            .or(row_created)
+            .and_then(|time| parse_notion_time(&time));
+        meta.updated = property_value(&markdown, &config.updated_property)
+            .map(String::from)
+            .or(row_updated)
+            .and_then(|time| parse_notion_time(&time));
+        let markdown = if config.inline_tags {
+            inline_property_tags(&markdown, &config.tag_property)
+        } else {
+            markdown
+        };
+        let title = page.hpath.rsplit('/').next();
+        let markdown = normalize_headings(&markdown, title, config);
+        let markdown = transform_markdown(&markdown, config)?;
+        // notion的附件链接是相对于页面所在目录的
+        let page_dir = page.file.parent().unwrap_or(dir);
+        let mut markdown = upload_local_assets(&markdown, api, page_dir, dir).await?;
+        if let Some(cache_dir) = &config.image_cache_dir {
+            markdown = localize_images(&markdown, api, cache_dir).await?;
+        }
+        let markdown = update_document_attachments(&markdown);
+        let time = match meta.created {
+            Some(created) if config.timestamp_ids => created,
+            _ => Local::now().naive_local(),
+        };
+        let id = timestamp_id(time, &page.hpath);
+        if let Some(title) = title {
+            api.add_doc_title(title, &id).await;
+        }
+        docs.push((page.hpath, markdown, id, tags, meta, page_dir.to_path_buf()));
+    }
+
+    let mut ids = Vec::new();
+    for (hpath, markdown, id, tags, meta, page_dir) in docs {
+        let markdown = update_page_mentions(&markdown, api).await?;
+        let id = api
+            .create_doc_with_md(&notebook, &hpath, &markdown, Some(&id))
+            .await?;
+        set_doc_tags(api, &id, &tags).await?;
+        set_page_meta(api, &id, &meta, Some(&page_dir)).await?;
+        ids.push(id);
+    }
+    Ok(ids)
+}
+
+/// 收集导出目录中的所有页面, 父页面排在子页面前面
+pub(crate) fn collect_pages(dir: &Path) -> Result<Vec<Page>> {
+    let pattern = format!("{}/**/*", Pattern::escape(&dir.to_string_lossy()));
+    let mut pages = Vec::new();
+    for file in glob::glob(&pattern)?.flatten() {
+        let extension = file
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        if extension == "csv" {
+            // notion会同时导出当前视图的`xxx.csv`和所有行的`xxx_all.csv`, 只保留后者
+            let stem = file.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+            if file.with_file_name(format!("{}_all.csv", stem)).exists() {
+                continue;
+            }
+        } else if extension != "md" && extension != "html" {
+            continue;
+        }
+        if let Some(hpath) = page_hpath(file.strip_prefix(dir)?) {
+            pages.push(Page { hpath, file });
+        }
+    }
+    pages.sort_by(|a, b| {
+        let depth = |page: &Page| page.hpath.matches('/').count();
+        depth(a).cmp(&depth(b)).then(a.hpath.cmp(&b.hpath))
+    });
+    Ok(pages)
+}
+
+/// 把导出目录中的相对路径转换为siyuan的人类可读路径, 例如:
+/// `Parent 1a2b...3c4d/Child 5e6f...7a8b.md` => `/Parent/Child`
+pub(crate) fn page_hpath(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+    let stem = stem.strip_suffix("_all").unwrap_or(stem);
+    let mut parts = Vec::new();
+    for component in path.parent()?.components() {
+        let name = component.as_os_str().to_str()?;
+        // zip的最外层目录不是页面
+        if name.starts_with("Export-") {
+            continue;
+        }
+        parts.push(strip_notion_id(name));
+    }
+    parts.push(strip_notion_id(stem));
+    Some(format!("/{}", parts.join("/")))
+}
+
+/// 去掉notion在文件名后面添加的页面id
+pub(crate) fn strip_notion_id(name: &str) -> String {
+    let re = Regex::new(r"\s+[0-9a-f]{32}$").unwrap();
+    re.replace(name, "").trim().to_string()
+}
+
+/// 读取页面内容, 数据库的csv转换为markdown表格, html转换为kramdown
+fn read_page(path: &Path) -> Result<String> {
+    let data = fs::read_to_string(path)?;
+    let data = data.trim_start_matches('\u{feff}');
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("csv") => csv_to_markdown(data),
+        Some("html") => Ok(html_page_to_kramdown(data)),
+        _ => Ok(data.to_string()),
+    }
+}
+
+/// 把notion数据库导出的csv转换为markdown表格
+pub(crate) fn csv_to_markdown(data: &str) -> Result<String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(data.as_bytes());
+    let escape = |cell: &str| cell.replace('|', "\\|").replace('\n', "<br>");
+
+    let headers = reader.headers()?.iter().map(escape).collect::<Vec<_>>();
+    let mut lines = vec![
+        format!("| {} |", headers.join(" | ")),
+        format!("|{}", " --- |".repeat(headers.len())),
+    ];
+    for record in reader.records() {
+        let record = record?;
+        let mut cells = record.iter().map(escape).collect::<Vec<_>>();
+        cells.resize(headers.len(), String::new());
+        lines.push(format!("| {} |", cells.join(" | ")));
+    }
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_hpath() {
+        let path = Path::new(
+            "Export-1234/Parent 0123456789abcdef0123456789abcdef/Child fedcba9876543210fedcba9876543210.md",
+        );
+        assert_eq!(page_hpath(path).unwrap(), "/Parent/Child");
+        let path = Path::new("Tasks 0123456789abcdef0123456789abcdef_all.csv");
+        assert_eq!(page_hpath(path).unwrap(), "/Tasks");
+    }
+
+    #[test]
+    fn test_extract_zip() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let zip_bytes = |files: &[(&str, &[u8])]| {
+            let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+            for (name, data) in files {
+                writer
+                    .start_file(*name, SimpleFileOptions::default())
+                    .unwrap();
+                writer.write_all(data).unwrap();
+            }
+            writer.finish().unwrap().into_inner()
+        };
+        let part = zip_bytes(&[("Export/Page.md", b"# Page")]);
+        let attachment = zip_bytes(&[("inside.txt", b"data")]);
+        let outer = zip_bytes(&[
+            ("Export-Part-1.zip", &part),
+            ("Export/Other.md", b"# Other"),
+            ("Export/Other/files.zip", &attachment),
+        ]);
+
+        let root = std::env::temp_dir().join("siyuan-notion-importer-zip-test");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let path = root.join("export.zip");
+        fs::write(&path, outer).unwrap();
+        let dir = root.join("out");
+        extract_zip(&path, &dir).unwrap();
+        assert!(dir.join("Export/Page.md").is_file());
+        assert!(dir.join("Export/Other.md").is_file());
+        assert!(!dir.join("Export-Part-1.zip").exists());
+        // 页面中的zip附件不解压
+        assert!(dir.join("Export/Other/files.zip").is_file());
+        assert!(!dir.join("Export/Other/inside.txt").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_csv_to_markdown() {
+        let data = "Name,Tags\nFirst,\"a|b\"\nSecond\n";
+        let target = "| Name | Tags |\n| --- | --- |\n| First | a\\|b |\n| Second |  |";
+        assert_eq!(csv_to_markdown(data).unwrap(), target);
+    }
+}
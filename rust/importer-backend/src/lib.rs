@@ -1,5 +1,28 @@
 mod api;
+mod asset;
+mod attachment;
 mod block;
+mod child_page;
+mod cleanup;
+mod color;
+mod config;
+mod dedup;
+mod embed;
+mod footnote;
+mod heading;
+mod html;
+mod image;
+mod importer;
+mod list;
+mod markdown;
+mod math;
+mod mention;
+mod meta;
 mod notebook;
+mod notion_json;
+mod table;
+mod tag;
+mod toc;
 
+pub use config::{ChildLinkMode, Config, HeadingLevelMode, NavBlockMode};
 pub use notebook::Notebook;
@@ -0,0 +1,381 @@
+use crate::api::Api;
+use crate::cleanup::fence_run;
+use anyhow::Result;
+use fancy_regex::{Captures, Regex};
+use serde_json::Value;
+
+/// 列表项标记, 捕获(缩进, 标记, 标记后的空格)
+const MARKER: &str = r"^([ \t]*)([*+-]|\d{1,9}[.)])([ \t]+|$)";
+
+/// 可以作为notion编号列表项子块的块类型, 这些块导出时没有缩进, 会打断编号列表
+const CHILD_BLOCK_TYPES: [&str; 6] = [
+    "NodeCodeBlock",
+    "NodeMathBlock",
+    "NodeTable",
+    "NodeHTMLBlock",
+    "NodeBlockquote",
+    "NodeParagraph",
+];
+
+/// 按照缩进修复列表的嵌套
+///
+/// notion导出的子列表可能使用tab或者比父列表项内容更少的缩进, 这里按照缩进的相对大小确定层级,
+/// 然后把每个列表项缩进到父列表项内容的位置, 列表项中其他行保持相对于列表项内容的缩进.
+/// 代码块中的行保留原来的空白, 只增加或者去掉列表层级的缩进
+pub(crate) fn repair_list_nesting(data: &str) -> String {
+    let marker_re = Regex::new(MARKER).unwrap();
+    // (原来的缩进, 原来内容的位置, 新的内容位置)
+    let mut stack: Vec<(usize, usize, usize)> = Vec::new();
+    // 代码块开始的fence, 结束的fence至少和它一样长
+    let mut fence: Option<String> = None;
+    let mut lines = Vec::new();
+    for line in data.lines() {
+        let indent = indent_width(line);
+        let content = line.trim_start();
+        let shift = |stack: &[(usize, usize, usize)]| match stack.last() {
+            Some((_, old, new)) => new + indent.saturating_sub(*old),
+            None => indent,
+        };
+        if let Some(f) = &fence {
+            let trimmed = line.trim();
+            if fence_run(trimmed).is_some_and(|r| r.len() == trimmed.len() && r.starts_with(f)) {
+                fence = None;
+            }
+            let (old, new) = stack
+                .last()
+                .map(|(_, old, new)| (*old, *new))
+                .unwrap_or((0, 0));
+            lines.push(shift_line(line, old, new));
+            continue;
+        }
+        let cap = match marker_re.captures(line) {
+            Ok(Some(cap)) => cap,
+            _ => {
+                // 缩进小于列表项内容位置的行不属于这个列表项
+                while !content.is_empty() && stack.last().is_some_and(|(_, old, _)| *old > indent) {
+                    stack.pop();
+                }
+                fence = fence_run(content).map(String::from);
+                let line = reindent(content, shift(&stack), line.trim().is_empty());
+                lines.push(line.trim_end().to_string());
+                continue;
+            }
+        };
+        while stack.last().is_some_and(|(old, _, _)| *old >= indent) {
+            stack.pop();
+        }
+        let new_indent = stack.last().map(|(_, _, new)| *new).unwrap_or(0);
+        let marker = &cap[2];
+        let spaces = cap[3].len().max(1);
+        stack.push((
+            indent,
+            indent + marker.len() + spaces,
+            new_indent + marker.len() + 1,
+        ));
+        let item = format!(
+            "{}{} {}",
+            " ".repeat(new_indent),
+            marker,
+            &line[cap[0].len()..]
+        );
+        lines.push(item.trim_end().to_string());
+    }
+    lines.join("\n")
+}
+
+/// 把代码块中的行从列表项内容的位置`old`移动到`new`, 只改变行首的空白
+fn shift_line(line: &str, old: usize, new: usize) -> String {
+    if new >= old {
+        return format!("{}{}", " ".repeat(new - old), line);
+    }
+    // 去掉宽度为`old - new`的行首空白, 只需要去掉tab的一部分时把剩下的宽度换成空格
+    let mut remove = old - new;
+    let mut rest = line;
+    while remove > 0 {
+        match rest.chars().next() {
+            Some(' ') => remove -= 1,
+            Some('\t') if remove >= 4 => remove -= 4,
+            Some('\t') => return format!("{}{}", " ".repeat(4 - remove), &rest[1..]),
+            _ => break,
+        }
+        rest = &rest[1..];
+    }
+    rest.to_string()
+}
+
+/// 合并被子块打断的编号列表, `blocks`是(块类型, 块内容)
+///
+/// notion编号列表项的子块(代码块、图片等)导出时没有缩进, 后面的列表项会变成从1开始的新列表.
+/// 后一个列表从1开始时只合并中间只有一个子块的情况, 从上一个列表的下一个编号开始时合并中间的所有子块
+pub(crate) fn merge_ordered_lists(blocks: Vec<(&str, String)>) -> Vec<(&str, String)> {
+    let mut res: Vec<(&str, String)> = Vec::new();
+    let mut i = 0;
+    while i < blocks.len() {
+        let (node_type, block) = &blocks[i];
+        let last = res
+            .last()
+            .filter(|(t, b)| *t == "NodeList" && ordered_start(b).is_some())
+            .and_then(|(_, b)| last_item(b));
+        if let (Some((number, content)), true) = (last, CHILD_BLOCK_TYPES.contains(node_type)) {
+            let next = (i..blocks.len()).find(|j| !CHILD_BLOCK_TYPES.contains(&blocks[*j].0));
+            let start = next
+                .filter(|j| blocks[*j].0 == "NodeList")
+                .and_then(|j| ordered_start(&blocks[j].1));
+            let merge = match (next, start) {
+                (Some(next), Some(1)) => next == i + 1 && is_child_block(node_type, block),
+                (Some(_), Some(start)) => start == number + 1,
+                _ => false,
+            };
+            if let (Some(next), true) = (next, merge) {
+                let list = &mut res.last_mut().unwrap().1;
+                for (_, child) in &blocks[i..next] {
+                    list.push_str("\n\n");
+                    list.push_str(&indent_lines(child, content));
+                }
+                list.push_str("\n\n");
+                list.push_str(&renumber(&blocks[next].1, number + 1));
+                i = next + 1;
+                continue;
+            }
+        }
+        res.push((node_type, block.clone()));
+        i += 1;
+    }
+    res
+}
+
+/// 合并文档中被子块打断的编号列表
+///
+/// 在文档的其他块更新之后使用, 合并后的列表使用第一个列表的块id, 其他块删除之后由siyuan生成新的块
+pub(crate) async fn update_ordered_lists(doc: &Value, api: &Api) -> Result<()> {
+    let Some(children) = doc["Children"].as_array() else {
+        return Ok(());
+    };
+    let mut i = 0;
+    while i < children.len() {
+        let Some(list_idx) = children[i]["ID"]
+            .as_str()
+            .filter(|_| is_ordered(&children[i]))
+        else {
+            i += 1;
+            continue;
+        };
+        let next = (i + 1..children.len())
+            .find(|j| !CHILD_BLOCK_TYPES.contains(&children[*j]["Type"].as_str().unwrap_or("")));
+        let Some(next) = next.filter(|j| *j > i + 1 && is_ordered(&children[*j])) else {
+            i += 1;
+            continue;
+        };
+        let mut blocks = Vec::new();
+        for child in &children[i..=next] {
+            let Some(idx) = child["ID"].as_str() else {
+                break;
+            };
+            let kramdown = api.get_block_kramdown(idx).await?;
+            let node_type = child["Type"].as_str().unwrap_or("");
+            blocks.push((idx, (node_type, kramdown)));
+        }
+        let kramdowns = blocks.iter().map(|(_, block)| block.clone()).collect();
+        let merged = merge_block_kramdown(kramdowns).filter(|_| blocks.len() == next - i + 1);
+        if let Some(merged) = merged {
+            api.update_block(&merged, list_idx).await?;
+            for (idx, _) in &blocks[1..] {
+                api.delete_block(idx).await?;
+            }
+        }
+        i = next + 1;
+    }
+    Ok(())
+}
+
+/// 合并siyuan的kramdown, `blocks`是(块类型, kramdown), 第一个是列表, 能合并成一个列表时返回合并后的kramdown
+///
+/// 列表的ial在最后一行, 更新时第一个列表保留块id. 后面的块会被删除, 去掉它们ial中的id
+fn merge_block_kramdown(blocks: Vec<(&str, String)>) -> Option<String> {
+    let strip = |data: &str| {
+        data.lines()
+            .take(data.lines().count().saturating_sub(1))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let blocks = blocks
+        .into_iter()
+        .enumerate()
+        .map(|(i, (node_type, kramdown))| {
+            let data = match node_type {
+                "NodeList" => strip(&kramdown),
+                _ => kramdown,
+            };
+            (node_type, if i == 0 { data } else { strip_ids(&data) })
+        })
+        .collect();
+    match merge_ordered_lists(blocks).as_slice() {
+        [(_, merged)] => Some(merged.clone()),
+        _ => None,
+    }
+}
+
+/// 去掉ial中的`id`和`updated`, 只剩下这两个属性的ial整个删除
+fn strip_ids(data: &str) -> String {
+    let ial_re = Regex::new(r"\{:([^}]*)\}").unwrap();
+    let attr_re = Regex::new(r#"\s*\b(?:id|updated)="[^"]*""#).unwrap();
+    data.lines()
+        .filter_map(|line| {
+            let stripped = ial_re.replace_all(line, |cap: &Captures| {
+                let attrs = attr_re.replace_all(&cap[1], "");
+                if attrs.trim().is_empty() {
+                    String::new()
+                } else {
+                    format!("{{:{}}}", attrs)
+                }
+            });
+            // 只有ial的行删除, 原来的空行保留
+            if stripped.trim().is_empty() && !line.trim().is_empty() {
+                None
+            } else {
+                Some(stripped.to_string())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 后一个列表从1开始时, 中间的块是否像是列表项的子块, 普通的文本段落可能是有意重新编号的
+fn is_child_block(node_type: &str, data: &str) -> bool {
+    let data = data.trim_start();
+    node_type != "NodeParagraph" || data.starts_with("![") || data.starts_with('<')
+}
+
+/// siyuan的有序列表(`ListData.Typ`为1)
+fn is_ordered(data: &Value) -> bool {
+    data["Type"] == "NodeList" && data["ListData"]["Typ"] == 1
+}
+
+/// 有序列表第一项的编号, 不是有序列表时返回`None`
+fn ordered_start(data: &str) -> Option<usize> {
+    let marker_re = Regex::new(MARKER).unwrap();
+    let first = data.lines().find(|line| !line.trim().is_empty())?;
+    let cap = marker_re.captures(first).ok()??;
+    cap[2][..cap[2].len() - 1].parse().ok()
+}
+
+/// 最后一个顶层列表项的(编号, 内容位置)
+fn last_item(data: &str) -> Option<(usize, usize)> {
+    let marker_re = Regex::new(MARKER).unwrap();
+    data.lines()
+        .rev()
+        .filter_map(|line| marker_re.captures(line).ok().flatten())
+        .find(|cap| cap[1].is_empty())
+        .and_then(|cap| {
+            let number = cap[2][..cap[2].len() - 1].parse().ok()?;
+            Some((number, cap[2].len() + cap[3].len().max(1)))
+        })
+}
+
+/// 从`start`开始重新编号顶层列表项
+fn renumber(data: &str, start: usize) -> String {
+    let marker_re = Regex::new(r"^(\d{1,9})([.)])").unwrap();
+    let mut number = start;
+    data.lines()
+        .map(|line| match marker_re.captures(line) {
+            Ok(Some(cap)) => {
+                let line = format!("{}{}{}", number, &cap[2], &line[cap[0].len()..]);
+                number += 1;
+                line
+            }
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 给每一行加上`width`个空格的缩进, 保留原来的空白(代码块中的tab)
+fn indent_lines(data: &str, width: usize) -> String {
+    data.lines()
+        .map(|line| {
+            if line.trim().is_empty() {
+                String::new()
+            } else {
+                format!("{}{}", " ".repeat(width), line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn reindent(line: &str, width: usize, blank: bool) -> String {
+    if blank {
+        String::new()
+    } else {
+        format!("{}{}", " ".repeat(width), line.trim_start())
+    }
+}
+
+/// 行首空白的宽度, tab按照4个空格计算
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repair_list_nesting() {
+        let data = "1. First\n\t* Child\n\t  more\n2. Second\n - Flattened\n    - Deep";
+        assert_eq!(
+            repair_list_nesting(data),
+            "1. First\n   * Child\n     more\n2. Second\n   - Flattened\n     - Deep"
+        );
+        let data = "* {: id=\"a\"}One\n  * {: id=\"b\"}Two\n  {: id=\"c\"}";
+        assert_eq!(repair_list_nesting(data), data);
+        // 代码块中的tab和行尾空白保留, ````中的```不结束代码块
+        let data = "1. First\n\t* Child\n\t  ````\n\t  \tcode  \n\t  ```\n\t  ````";
+        assert_eq!(
+            repair_list_nesting(data),
+            "1. First\n   * Child\n     ````\n     \tcode  \n     ```\n     ````"
+        );
+    }
+
+    #[test]
+    fn test_merge_ordered_lists() {
+        let blocks = vec![
+            ("NodeList", "1. One\n2. Two".to_string()),
+            ("NodeCodeBlock", "```rust\nfn main() {}\n```".to_string()),
+            ("NodeList", "1. Three".to_string()),
+            ("NodeParagraph", "Text".to_string()),
+            ("NodeList", "1. New".to_string()),
+        ];
+        let merged = merge_ordered_lists(blocks);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(
+            merged[0].1,
+            "1. One\n2. Two\n\n   ```rust\n   fn main() {}\n   ```\n\n3. Three"
+        );
+        assert_eq!(merged[2].1, "1. New");
+    }
+
+    #[test]
+    fn test_merge_block_kramdown() {
+        let blocks = vec![
+            (
+                "NodeList",
+                "1. {: id=\"i1\"}One\n   {: id=\"p1\"}\n{: id=\"l1\" updated=\"20240101\"}"
+                    .to_string(),
+            ),
+            (
+                "NodeCodeBlock",
+                "```\n\tindented\n```\n{: id=\"c1\" style=\"color: red;\"}".to_string(),
+            ),
+            (
+                "NodeList",
+                "1. {: id=\"i2\"}Two\n   {: id=\"p2\"}\n{: id=\"l2\"}".to_string(),
+            ),
+        ];
+        let target = "1. {: id=\"i1\"}One\n   {: id=\"p1\"}\n\n   ```\n   \tindented\n   ```\n   {: style=\"color: red;\"}\n\n2. Two";
+        assert_eq!(merge_block_kramdown(blocks).unwrap(), target);
+    }
+}
@@ -0,0 +1,263 @@
+use crate::block::{
+    update_node_blockquote, update_node_code_block, update_node_html_block, update_node_list,
+    update_node_math_block, update_node_paragraph,
+};
+use crate::cleanup::{is_spacer, is_thematic_break, separate_thematic_breaks, trim_quote_lines};
+use crate::color::update_notion_colors;
+use crate::config::Config;
+use crate::embed::update_embeds;
+use crate::footnote::convert_footnotes;
+use crate::list::{merge_ordered_lists, repair_list_nesting};
+use crate::mention::update_mentions;
+use crate::table::update_node_table;
+use crate::toc::{update_nav_block, HEADINGS_EMBED};
+use anyhow::Result;
+use fancy_regex::Regex;
+
+/// 对整个markdown文档做和`update_data`相同的块转换
+///
+/// 块转换函数处理的是siyuan的kramdown, 每个块的最后一行都是ial, 所以这里给每个块加上一个空的ial
+pub(crate) fn transform_markdown(data: &str, config: &Config) -> Result<String> {
+    let data = if config.footnotes {
+        convert_footnotes(data)
+    } else {
+        data.to_string()
+    };
+    let data = separate_thematic_breaks(&data);
+    let mut res = Vec::new();
+    let mut previous_spacer = false;
+    for (node_type, block) in split_blocks(&data) {
+        // 连续的空段落只保留一个
+        let spacer = node_type == "NodeParagraph" && is_spacer(&block);
+        if spacer && previous_spacer {
+            continue;
+        }
+        previous_spacer = spacer;
+        // 静态的目录去掉标记之后和其他块一样转换
+        let block = match update_nav_block(&block, config) {
+            Some(nav) if nav.is_empty() => continue,
+            Some(nav) if nav == HEADINGS_EMBED => {
+                res.push((node_type, nav));
+                continue;
+            }
+            Some(nav) => nav,
+            None => block,
+        };
+        let kramdown = format!("{}\n{{: }}", block);
+        let block = match node_type {
+            "NodeParagraph" => {
+                update_embeds(&update_node_paragraph(&kramdown), &config.embed_providers)
+            }
+            "NodeMathBlock" => update_node_math_block(&kramdown),
+            "NodeBlockquote" => trim_quote_lines(&update_node_blockquote(&block)?),
+            "NodeThematicBreak" => "---".to_string(),
+            "NodeCodeBlock" => update_node_code_block(&kramdown),
+            "NodeHTMLBlock" => update_node_html_block(&kramdown)?.unwrap_or(block),
+            "NodeList" => repair_list_nesting(&update_node_list(&kramdown)),
+            "NodeTable" => update_node_table(&kramdown),
+            _ => block,
+        };
+        let block = match node_type {
+            "NodeCodeBlock" | "NodeMathBlock" | "NodeHTMLBlock" => block,
+            _ => update_mentions(&block, config),
+        };
+        res.push((node_type, update_notion_colors(&block, &config.colors)));
+    }
+    // 列表项的子块也已经转换, 最后合并被打断的编号列表
+    let res = merge_ordered_lists(res)
+        .into_iter()
+        .map(|(_, block)| block)
+        .collect::<Vec<_>>();
+    Ok(res.join("\n\n"))
+}
+
+/// 可以开始html块的块级标签, 不包括`<hr>`等空元素
+const HTML_BLOCK_TAGS: &str = "address article aside audio blockquote body caption center dd details \
+    dialog div dl dt fieldset figcaption figure footer form h1 h2 h3 h4 h5 h6 header iframe li main \
+    nav ol p pre section summary table tbody thead tr td ul video";
+
+/// 行首是块级开始标签时返回标签名, 自动链接(`<https://..>`)、空元素和自闭合的标签不是html块
+fn html_block_tag(line: &str) -> Option<String> {
+    let re = Regex::new(r"^<([a-zA-Z][a-zA-Z0-9]*)(?:\s[^>]*)?(/?)>").unwrap();
+    let cap = re.captures(line).ok()??;
+    let tag = cap[1].to_lowercase();
+    if !cap[2].is_empty() || !HTML_BLOCK_TAGS.split_whitespace().any(|t| t == tag) {
+        return None;
+    }
+    Some(tag)
+}
+
+/// 把markdown文档拆分为顶层块, 返回(siyuan的块类型, 块内容)
+pub(crate) fn split_blocks(data: &str) -> Vec<(&'static str, String)> {
+    let list_re = Regex::new(r"^\s{0,3}(?:[*+-]|\d+[.)])(?:\s|$)").unwrap();
+    let lines = data.lines().collect::<Vec<_>>();
+    let mut blocks = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.trim().is_empty() {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let trimmed = line.trim_start();
+        let node_type = if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            // 代码块到结束的fence为止
+            let fence_len = trimmed
+                .chars()
+                .take_while(|c| *c == '`' || *c == '~')
+                .count();
+            let fence = &trimmed[..fence_len];
+            i += 1;
+            while i < lines.len() && lines[i].trim() != fence {
+                i += 1;
+            }
+            i += 1;
+            "NodeCodeBlock"
+        } else if trimmed.trim_end() == "$$" {
+            i += 1;
+            while i < lines.len() && lines[i].trim() != "$$" {
+                i += 1;
+            }
+            i += 1;
+            "NodeMathBlock"
+        } else if is_thematic_break(line) {
+            i += 1;
+            "NodeThematicBreak"
+        } else if let Some(tag) = html_block_tag(trimmed) {
+            // html块到标签闭合为止, 没有闭合的标签到空行为止
+            let open = Regex::new(&format!(r"(?i)<{}(?=[\s>/])", tag)).unwrap();
+            let close = Regex::new(&format!(r"(?i)</{}\s*>", tag)).unwrap();
+            let closed = lines[i..]
+                .iter()
+                .any(|line| close.is_match(line).unwrap_or(false));
+            let mut depth = 0;
+            while i < lines.len() {
+                depth += open.find_iter(lines[i]).count() as i32;
+                depth -= close.find_iter(lines[i]).count() as i32;
+                i += 1;
+                if (depth <= 0 || !closed) && (i >= lines.len() || lines[i].trim().is_empty()) {
+                    break;
+                }
+            }
+            "NodeHTMLBlock"
+        } else if list_re.is_match(line).unwrap_or(false) {
+            // 列表项之间可能有空行, 缩进的行和后面的列表项都属于同一个列表
+            loop {
+                while i < lines.len() && !lines[i].trim().is_empty() {
+                    i += 1;
+                }
+                let next = (i..lines.len()).find(|j| !lines[*j].trim().is_empty());
+                match next {
+                    Some(next)
+                        if lines[next].starts_with([' ', '\t'])
+                            || list_re.is_match(lines[next]).unwrap_or(false) =>
+                    {
+                        i = next
+                    }
+                    _ => break,
+                }
+            }
+            "NodeList"
+        } else {
+            while i < lines.len() && !lines[i].trim().is_empty() {
+                i += 1;
+            }
+            if trimmed.starts_with('>') {
+                "NodeBlockquote"
+            } else if trimmed.starts_with('#') {
+                "NodeHeading"
+            } else if trimmed.starts_with('|') {
+                "NodeTable"
+            } else {
+                "NodeParagraph"
+            }
+        };
+        let end = i.min(lines.len());
+        blocks.push((node_type, lines[start..end].join("\n")));
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::NavBlockMode;
+
+    #[test]
+    fn test_split_blocks() {
+        let data = r#"# Title
+
+Some text
+second line
+
+```Shell
+
+ls -al
+```
+
+- item 1
+
+  child paragraph
+- item 2
+
+$$
+x^2
+$$
+> quote"#;
+        let blocks = split_blocks(data);
+        let types = blocks.iter().map(|(t, _)| *t).collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                "NodeHeading",
+                "NodeParagraph",
+                "NodeCodeBlock",
+                "NodeList",
+                "NodeMathBlock",
+                "NodeBlockquote"
+            ]
+        );
+        assert_eq!(blocks[2].1, "```Shell\n\nls -al\n```");
+        assert_eq!(blocks[3].1, "- item 1\n\n  child paragraph\n- item 2");
+
+        // 自动链接、空元素和没有闭合的标签不会吞掉后面的块
+        let data = "<https://example.com>\n\n<img src=\"a.png\">\n\n<div>open\n\n# Heading";
+        let types = split_blocks(data)
+            .iter()
+            .map(|(t, _)| *t)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                "NodeParagraph",
+                "NodeParagraph",
+                "NodeHTMLBlock",
+                "NodeHeading"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transform_markdown() {
+        let data = "Price \\$5 or \\$x\\$\n\n```Plain Text\nhello\n```\n\n- ☐ todo";
+        let target = "Price \\$5 or $x$\n\n```plaintext\nhello\n```\n\n- [ ] todo";
+        assert_eq!(
+            transform_markdown(data, &Config::default()).unwrap(),
+            target
+        );
+        let config = Config {
+            table_of_contents: NavBlockMode::Static,
+            ..Default::default()
+        };
+        let data = "- ☐ Intro\n{: custom-notion-block=\"table_of_contents\"}";
+        assert_eq!(transform_markdown(data, &config).unwrap(), "- [ ] Intro");
+        let data = "<span class=\"highlight-red\">Red</span>\n{: class=\"block-color-default\"}";
+        let target = "<span data-type=\"text\" style=\"color: #d44c47;\">Red</span>";
+        assert_eq!(
+            transform_markdown(data, &Config::default()).unwrap(),
+            target
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use fancy_regex::{Captures, Regex};
+
+/// 把notion导出的各种行内公式统一转换为siyuan的行内公式`$...$`
+///
+/// notion的行内公式可能是`\$...\$`(被siyuan转义)、代码中的`` `$...$` ``、`\(...\)`、`\[...\]`或者`$$...$$`,
+/// 货币符号(例如: `\$5 and \$10`)不会被转换. siyuan会把普通的括号转义为`\[1\]`,
+/// 所以`\(...\)`和`\[...\]`只转换像公式的内容
+pub(crate) fn normalize_inline_math(data: &str) -> String {
+    let code_re = Regex::new(r"(?<!`)`\$(\S(?:[^`$]*?\S)?)\$`(?!`)").unwrap();
+    let paren_re = Regex::new(r"\\\((.+?)\\\)").unwrap();
+    let bracket_re = Regex::new(r"\\\[(.+?)\\\]").unwrap();
+    let double_re = Regex::new(r"(?<![\\$])\$\$(\S(?:[^$]*?\S)?)\$\$").unwrap();
+    // 和pandoc一样, 公式的开头和结尾不能是空格, 结尾后面不能是数字
+    let escaped_re = Regex::new(r"\\\$(\S(?:[^$]*?\S)?)\\\$(?!\d)").unwrap();
+
+    let inline = |cap: &Captures| format!("${}$", cap[1].trim());
+    let delimited = |cap: &Captures| {
+        if is_math_like(&cap[1]) {
+            format!("${}$", unescape_backslashes(cap[1].trim()))
+        } else {
+            cap[0].to_string()
+        }
+    };
+    let data = code_re.replace_all(data, inline);
+    let data = paren_re.replace_all(&data, delimited);
+    let data = bracket_re.replace_all(&data, delimited);
+    let data = double_re.replace_all(&data, inline);
+    let data = escaped_re.replace_all(&data, |cap: &Captures| {
+        format!("${}$", unescape_backslashes(&cap[1]))
+    });
+    data.to_string()
+}
+
+/// 如果整个段落只有一个公式(`$$...$$`, `\$\$...\$\$`或者`\[...\]`), 转换为公式块
+pub(crate) fn to_math_block(data: &str) -> Option<String> {
+    let re = Regex::new(r"(?s)^(?:\$\$(.+)\$\$|\\\$\\\$(.+)\\\$\\\$|\\\[(.+)\\\])$").unwrap();
+    let cap = re.captures(data.trim()).ok()??;
+    let math = match (cap.get(1), cap.get(2), cap.get(3)) {
+        (Some(m), _, _) | (_, Some(m), _) => m.as_str(),
+        (_, _, Some(m)) if is_math_like(m.as_str()) => m.as_str(),
+        _ => return None,
+    };
+    let math = unescape_backslashes(math);
+    // 内容中还有`$`说明是多个行内公式, 例如: `$$a$$ and $$b$$`
+    if math.contains('$') || math.trim().is_empty() {
+        return None;
+    }
+    Some(format!("$$\n{}\n$$", math.trim()))
+}
+
+/// `\(...\)`和`\[...\]`中的内容是否像公式: 包含tex命令、上下标、运算符或者花括号, 或者是单个字母
+fn is_math_like(data: &str) -> bool {
+    let data = data.trim();
+    let mut chars = data.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => c.is_ascii_alphabetic(),
+        (Some(_), Some(_)) => data.contains(['\\', '^', '_', '=', '+', '{', '}']),
+        _ => false,
+    }
+}
+
+/// 去掉siyuan转义时添加的反斜杠, 例如: `\\alpha` => `\alpha`
+fn unescape_backslashes(data: &str) -> String {
+    let re = Regex::new(r"\\(\\*)").unwrap();
+    re.replace_all(data, "$1").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_inline_math() {
+        let data = r"Costs \$5 and \$10, \$\\alpha\$ `$x^2$` \(a+b\) \[c\] \(\\beta\) $$d$$ `code`";
+        let target = r"Costs \$5 and \$10, $\alpha$ $x^2$ $a+b$ $c$ $\beta$ $d$ `code`";
+        assert_eq!(normalize_inline_math(data), target);
+        let data = r"\[1\] \[note\] \[ \] \(see above\)";
+        assert_eq!(normalize_inline_math(data), data);
+    }
+
+    #[test]
+    fn test_to_math_block() {
+        assert_eq!(to_math_block("$$x^2$$").unwrap(), "$$\nx^2\n$$");
+        assert_eq!(to_math_block(r"\[ \\sum x \]").unwrap(), "$$\n\\sum x\n$$");
+        assert_eq!(
+            to_math_block(r"\$\$\\sum x\$\$").unwrap(),
+            "$$\n\\sum x\n$$"
+        );
+        assert!(to_math_block("$$a$$ and $$b$$").is_none());
+        assert!(to_math_block("Price $$5").is_none());
+        assert!(to_math_block(r"\[1\]").is_none());
+    }
+}
@@ -0,0 +1,170 @@
+use crate::api::Api;
+use crate::config::Config;
+use crate::importer::strip_notion_id;
+use anyhow::Result;
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime};
+use fancy_regex::{Captures, Regex};
+use percent_encoding::percent_decode_str;
+
+/// 转换日期提及和用户提及, 相对日期以导出的日期为准
+pub(crate) fn update_mentions(data: &str, config: &Config) -> String {
+    let today = config
+        .export_date
+        .unwrap_or_else(|| Local::now().date_naive());
+    let data = update_date_mentions(data, config.date_mention_mark, today);
+    update_user_mentions(&data, &config.user_mention_format)
+}
+
+/// 把notion的日期提及(例如: `@Today`, `@March 3, 2024`)转换为iso日期, 例如: `2024-03-03`
+///
+/// `as_mark`为true时使用`custom-date`行内元素包裹日期, 相对日期以`today`为准
+pub(crate) fn update_date_mentions(data: &str, as_mark: bool, today: NaiveDate) -> String {
+    let months = "January|February|March|April|May|June|July|August|September|October|November|December|Jan|Feb|Mar|Apr|Jun|Jul|Aug|Sept|Sep|Oct|Nov|Dec";
+    let re = Regex::new(&format!(
+        r"(?<![\w@])@(Today|Yesterday|Tomorrow|(?:{})\.? \d{{1,2}}, \d{{4}}(?: \d{{1,2}}:\d{{2}} ?(?:AM|PM))?)(?!\w)",
+        months
+    ))
+    .unwrap();
+    re.replace_all(data, |cap: &Captures| {
+        let Some(date) = parse_date(&cap[1], today) else {
+            return cap[0].to_string();
+        };
+        if as_mark {
+            format!(r#"<span data-type="custom-date">{}</span>"#, date)
+        } else {
+            date
+        }
+    })
+    .to_string()
+}
+
+/// 解析notion的日期, 有时间的返回`2024-03-03 15:00`
+fn parse_date(date: &str, today: NaiveDate) -> Option<String> {
+    match date {
+        "Today" => return Some(today.to_string()),
+        "Yesterday" => return Some((today - Duration::days(1)).to_string()),
+        "Tomorrow" => return Some((today + Duration::days(1)).to_string()),
+        _ => {}
+    }
+    // chrono不认识`Sept`和月份缩写后面的`.`
+    let date = date.replace("Sept", "Sep").replace('.', "");
+    for format in [
+        "%B %d, %Y %I:%M %p",
+        "%b %d, %Y %I:%M %p",
+        "%B %d, %Y %I:%M%p",
+    ] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(&date, format) {
+            return Some(datetime.format("%Y-%m-%d %H:%M").to_string());
+        }
+    }
+    for format in ["%B %d, %Y", "%b %d, %Y"] {
+        if let Ok(date) = NaiveDate::parse_from_str(&date, format) {
+            return Some(date.to_string());
+        }
+    }
+    None
+}
+
+/// 把notion的用户提及(例如: `@Jane Doe`)转换为配置的格式, `format`中的`{name}`会被替换为用户名,
+/// 例如: `#{name}#`转换为标签
+pub(crate) fn update_user_mentions(data: &str, format: &str) -> String {
+    if format == "@{name}" {
+        return data.to_string();
+    }
+    let re = Regex::new(r"(?<![\w@.])@([A-Z][\w'-]*(?: [A-Z][\w'-]*)?)(?![\w@])").unwrap();
+    re.replace_all(data, |cap: &Captures| format.replace("{name}", &cap[1]))
+        .to_string()
+}
+
+/// 查找页面提及, 返回(链接, 页面标题), 页面提及是指向notion页面的链接:
+/// 导出目录中的`Page%201a2b...3c4d.md`或者`https://www.notion.so/Page-1a2b...3c4d`
+pub(crate) fn find_page_mentions(data: &str) -> Vec<(String, String)> {
+    let re = Regex::new(r"(?<!!)\[([^\]]+)\]\(([^)\s]+)\)").unwrap();
+    let id_re = Regex::new(r"[0-9a-f]{32}").unwrap();
+    re.captures_iter(data)
+        .flatten()
+        .filter_map(|cap| {
+            let (text, target) = (&cap[1], &cap[2]);
+            if !id_re.is_match(target).unwrap_or(false) {
+                return None;
+            }
+            let title = if target.starts_with("http") {
+                if !target.contains("notion.so/") && !target.contains("notion.site/") {
+                    return None;
+                }
+                text.to_string()
+            } else {
+                let target = percent_decode_str(target).decode_utf8_lossy();
+                let (stem, ext) = target.rsplit_once('.')?;
+                if ext != "md" && ext != "html" {
+                    return None;
+                }
+                strip_notion_id(stem.rsplit('/').next().unwrap_or(stem))
+            };
+            Some((cap[0].to_string(), title))
+        })
+        .collect()
+}
+
+/// 把页面提及转换为块引用, 例如: `((20210808180117-6v0mkxr "Page"))`, 找不到或者有多个同名文档时保留链接
+pub(crate) async fn update_page_mentions(data: &str, api: &Api) -> Result<String> {
+    let mut data = data.to_string();
+    for (link, title) in find_page_mentions(&data) {
+        if let Some(id) = api.doc_id_by_title(&title).await? {
+            let anchor = title.replace('"', "&quot;");
+            data = data.replace(&link, &format!("(({} \"{}\"))", id, anchor));
+        }
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_date_mentions() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        let data =
+            "Due @March 3, 2024 and @Sept. 9, 2024 5:30 PM, moved from @Yesterday, mail a@b.com";
+        let target = "Due 2024-03-03 and 2024-09-09 17:30, moved from 2024-03-09, mail a@b.com";
+        assert_eq!(update_date_mentions(data, false, today), target);
+        assert_eq!(
+            update_date_mentions("@Today", true, today),
+            r#"<span data-type="custom-date">2024-03-10</span>"#
+        );
+        let config = Config {
+            export_date: Some(today),
+            ..Default::default()
+        };
+        assert_eq!(update_mentions("@Tomorrow", &config), "2024-03-11");
+    }
+
+    #[test]
+    fn test_update_user_mentions() {
+        let data = "Ask @Jane Doe or @bob, not jane@example.com";
+        assert_eq!(
+            update_user_mentions(data, "#{name}#"),
+            "Ask #Jane Doe# or @bob, not jane@example.com"
+        );
+    }
+
+    #[test]
+    fn test_find_page_mentions() {
+        let data = "See [Plan](Plan%200123456789abcdef0123456789abcdef.md), [Spec](https://www.notion.so/Spec-fedcba9876543210fedcba9876543210) and [Site](https://example.com)";
+        assert_eq!(
+            find_page_mentions(data),
+            vec![
+                (
+                    "[Plan](Plan%200123456789abcdef0123456789abcdef.md)".to_string(),
+                    "Plan".to_string()
+                ),
+                (
+                    "[Spec](https://www.notion.so/Spec-fedcba9876543210fedcba9876543210)"
+                        .to_string(),
+                    "Spec".to_string()
+                ),
+            ]
+        );
+    }
+}
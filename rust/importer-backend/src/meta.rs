@@ -0,0 +1,164 @@
+use crate::api::Api;
+use crate::asset::resolve_local_asset;
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use fancy_regex::Regex;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::path::Path;
+
+/// notion页面的图标、封面和时间
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct PageMeta {
+    /// emoji图标, 例如: `🎉`
+    pub(crate) icon: Option<String>,
+    /// 封面图片的链接, 可能是网址或者相对于页面的本地路径
+    pub(crate) cover: Option<String>,
+    /// 创建时间(`Created time`)
+    pub(crate) created: Option<NaiveDateTime>,
+    /// 最后编辑时间(`Last edited time`)
+    pub(crate) updated: Option<NaiveDateTime>,
+}
+
+/// 解析notion的时间, 支持api的`2024-03-03T15:04:00.000Z`和导出的`March 3, 2024 3:04 PM`, 返回本地时间
+pub(crate) fn parse_notion_time(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.with_timezone(&Local).naive_local());
+    }
+    let value = value.replace("Sept", "Sep");
+    let formats = [
+        "%B %d, %Y %I:%M %p",
+        "%b %d, %Y %I:%M %p",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y/%m/%d %H:%M",
+    ];
+    for format in formats {
+        if let Ok(time) = NaiveDateTime::parse_from_str(&value, format) {
+            return Some(time);
+        }
+    }
+    for format in ["%B %d, %Y", "%b %d, %Y", "%Y-%m-%d", "%Y/%m/%d"] {
+        if let Ok(date) = NaiveDate::parse_from_str(&value, format) {
+            return date.and_hms_opt(0, 0, 0);
+        }
+    }
+    None
+}
+
+/// 生成以时间为前缀的siyuan块id, 例如: `20240303150400-a1b2c3d`
+///
+/// 后缀由`seed`和随机的hash key计算, 重新导入同一个页面时不会使用已经存在的id
+pub(crate) fn timestamp_id(time: NaiveDateTime, seed: &str) -> String {
+    let mut hash = RandomState::new().hash_one(seed);
+    let chars = b"0123456789abcdefghijklmnopqrstuvwxyz";
+    let suffix = (0..7)
+        .map(|_| {
+            let c = chars[(hash % 36) as usize] as char;
+            hash /= 36;
+            c
+        })
+        .collect::<String>();
+    format!("{}-{}", time.format("%Y%m%d%H%M%S"), suffix)
+}
+
+/// notion导出的markdown没有图标和封面, 封面会作为标题后面的第一张图片, 返回封面和去掉封面之后的markdown
+pub(crate) fn markdown_cover(data: &str) -> (Option<String>, String) {
+    let re = Regex::new(r"^(\s*(?:#\s[^\n]*\n\s*)?)!\[[^\]]*]\(([^)\s]+)\)[ \t]*(?:\n|$)").unwrap();
+    match re.captures(data) {
+        Ok(Some(cap)) => {
+            let rest = format!("{}{}", &cap[1], &data[cap[0].len()..]);
+            (Some(cap[2].to_string()), rest)
+        }
+        _ => (None, data.to_string()),
+    }
+}
+
+/// 把emoji转换为siyuan的图标, siyuan使用emoji的unicode编码, 例如: `🎉` => `1f389`
+pub(crate) fn emoji_icon(emoji: &str) -> String {
+    emoji
+        .chars()
+        .map(|c| format!("{:x}", c as u32))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// 把图标、封面和时间写入文档的`icon`, `title-img`, `created`和`updated`属性, 本地的封面会上传到附件中
+pub(crate) async fn set_page_meta(
+    api: &Api,
+    idx: &str,
+    meta: &PageMeta,
+    page_dir: Option<&Path>,
+) -> Result<()> {
+    let mut attrs = HashMap::new();
+    if let Some(icon) = meta.icon.as_deref().filter(|icon| !icon.trim().is_empty()) {
+        attrs.insert("icon".to_string(), emoji_icon(icon.trim()));
+    }
+    if let Some(cover) = &meta.cover {
+        let url = if cover.starts_with("http://") || cover.starts_with("https://") {
+            Some(cover.clone())
+        } else if cover.starts_with("/images/") {
+            // notion自带的封面
+            Some(format!("https://www.notion.so{}", cover))
+        } else {
+            match page_dir.and_then(|dir| resolve_local_asset(cover, dir, dir)) {
+                Some(path) => Some(api.upload_asset_once(&path).await?),
+                None => None,
+            }
+        };
+        if let Some(url) = url {
+            attrs.insert(
+                "title-img".to_string(),
+                format!("background-image:url(\"{}\")", url),
+            );
+        }
+    }
+    if let Some(created) = meta.created {
+        attrs.insert(
+            "created".to_string(),
+            created.format("%Y%m%d%H%M%S").to_string(),
+        );
+    }
+    if let Some(updated) = meta.updated.or(meta.created) {
+        attrs.insert(
+            "updated".to_string(),
+            updated.format("%Y%m%d%H%M%S").to_string(),
+        );
+    }
+    if attrs.is_empty() {
+        return Ok(());
+    }
+    api.set_block_attrs(idx, &attrs).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_cover() {
+        let data = "# Page\n\n![](Page/cover.png)\n\nText";
+        let (cover, rest) = markdown_cover(data);
+        assert_eq!(cover.unwrap(), "Page/cover.png");
+        assert_eq!(rest, "# Page\n\n\nText");
+        assert_eq!(markdown_cover("Text\n\n![](a.png)").0, None);
+    }
+
+    #[test]
+    fn test_parse_notion_time() {
+        let time = parse_notion_time("March 3, 2024 3:04 PM").unwrap();
+        assert_eq!(time.to_string(), "2024-03-03 15:04:00");
+        let time = parse_notion_time("2024-03-03").unwrap();
+        assert!(timestamp_id(time, "/Page").starts_with("20240303000000-"));
+        assert_eq!(timestamp_id(time, "/Page").len(), 22);
+        assert_ne!(timestamp_id(time, "/Page"), timestamp_id(time, "/Page"));
+    }
+
+    #[test]
+    fn test_emoji_icon() {
+        assert_eq!(emoji_icon("🎉"), "1f389");
+        assert_eq!(emoji_icon("👨‍💻"), "1f468-200d-1f4bb");
+    }
+}
@@ -1,22 +1,123 @@
 use crate::api::Api;
-use crate::block::{update_node_blockquote, update_node_math_block, update_node_paragraph};
+use crate::asset::upload_local_assets;
+use crate::attachment::update_attachments;
+use crate::block::{
+    update_code_caption, update_node_blockquote, update_node_code_block, update_node_html_block,
+    update_node_list, update_node_math_block, update_node_paragraph,
+};
+use crate::child_page::update_child_page_links;
+use crate::cleanup::{
+    is_thematic_break, remove_blank_quote_lines, remove_empty_paragraphs, split_setext_heading,
+    trim_quote_lines,
+};
+use crate::color::update_notion_colors;
+use crate::config::Config;
+use crate::dedup::{block_text, dedup_documents};
+use crate::embed::update_embeds;
+use crate::heading::update_headings;
+use crate::image::localize_images;
+use crate::importer::import_zip;
+use crate::list::{repair_list_nesting, update_ordered_lists};
+use crate::mention::{update_mentions, update_page_mentions};
+use crate::meta::{parse_notion_time, set_page_meta, PageMeta};
+use crate::notion_json::import_json_dir;
+use crate::table::update_node_table;
+use crate::tag::{inline_property_tags, merge_tags, property_tags, property_value, set_doc_tags};
+use crate::toc::update_nav_block;
 use anyhow::Result;
 use serde_json::Value;
+use std::path::Path;
 use std::sync::Arc;
 use tokio::fs;
 use tokio::runtime::Runtime;
 use tokio::sync::Mutex;
 
-async fn update_data(data: &Value, api: &Api) -> Result<()> {
+async fn update_data(data: &Value, api: &Api, config: &Config) -> Result<()> {
     if let Some(data_type) = data["Type"].as_str() {
         match data_type {
             "NodeParagraph" => {
                 if let Some(idx) = data["ID"].as_str() {
                     let markdown_data = api.get_block_kramdown(idx).await?;
+                    // 只有分隔线的段落转换为分隔线块
+                    let text = markdown_data.lines().next().unwrap_or("");
+                    if markdown_data.lines().count() == 2 && is_thematic_break(text) {
+                        api.update_block("---", idx).await?;
+                        return Ok(());
+                    }
                     let markdown_data = update_node_paragraph(&markdown_data);
+                    let markdown_data = update_embeds(&markdown_data, &config.embed_providers);
+                    let markdown_data = update_mentions(&markdown_data, config);
+                    let markdown_data = update_page_mentions(&markdown_data, api).await?;
+                    let mut markdown_data = update_notion_colors(&markdown_data, &config.colors);
+                    if let Some(export_dir) = &config.export_dir {
+                        markdown_data =
+                            upload_local_assets(&markdown_data, api, export_dir, export_dir)
+                                .await?;
+                    }
+                    if let Some(cache_dir) = &config.image_cache_dir {
+                        markdown_data = localize_images(&markdown_data, api, cache_dir).await?;
+                    }
+                    let markdown_data = update_attachments(&markdown_data);
                     api.update_block(&markdown_data, idx).await?;
                 }
             }
+            "NodeDocument" => {
+                // 页面属性在文档开头的段落中, 前面可能有和文档同名的标题
+                let paragraph = data["Children"].as_array().and_then(|children| {
+                    let title = children.first().is_some_and(|c| c["Type"] == "NodeHeading");
+                    children.get(usize::from(title))
+                });
+                let paragraph = paragraph.filter(|child| child["Type"] == "NodeParagraph");
+                let mut meta = None;
+                if let (Some(idx), Some(paragraph)) = (data["ID"].as_str(), paragraph) {
+                    let text = block_text(paragraph);
+                    let tags = property_tags(&text, &config.tag_property);
+                    if !tags.is_empty() {
+                        let existing = data["Properties"]["tags"].as_str().unwrap_or("");
+                        set_doc_tags(api, idx, &merge_tags(existing, &tags)).await?;
+                    }
+                    let time = |property: &str| {
+                        property_value(&text, property).and_then(parse_notion_time)
+                    };
+                    meta = Some(PageMeta {
+                        created: time(&config.created_property),
+                        updated: time(&config.updated_property),
+                        ..Default::default()
+                    });
+                    if config.inline_tags && !tags.is_empty() {
+                        if let Some(paragraph_idx) = paragraph["ID"].as_str() {
+                            let markdown_data = api.get_block_kramdown(paragraph_idx).await?;
+                            let markdown_data = markdown_data
+                                .lines()
+                                .take(markdown_data.lines().count().saturating_sub(1))
+                                .collect::<Vec<_>>()
+                                .join("\n");
+                            let markdown_data =
+                                inline_property_tags(&markdown_data, &config.tag_property);
+                            api.update_block(&markdown_data, paragraph_idx).await?;
+                        }
+                    }
+                }
+                // 删除的子文档链接和标题不再需要更新
+                let mut removed =
+                    update_child_page_links(data, api, &config.child_page_links).await?;
+                removed.extend(update_headings(data, api, config).await?);
+                removed.extend(remove_empty_paragraphs(data, api).await?);
+                let mut data = data.clone();
+                if let Some(children) = data["Children"].as_array_mut() {
+                    children.retain(|child| {
+                        !removed
+                            .iter()
+                            .any(|idx| child["ID"].as_str() == Some(idx.as_str()))
+                    });
+                }
+                update_children(&data, api, config).await?;
+                update_ordered_lists(&data, api).await?;
+                // 更新子块会修改文档的`updated`, 最后再写入时间
+                if let (Some(idx), Some(meta)) = (data["ID"].as_str(), meta) {
+                    set_page_meta(api, idx, &meta, None).await?;
+                }
+            }
             "NodeMathBlock" => {
                 if let Some(idx) = data["ID"].as_str() {
                     let markdown_data = api.get_block_kramdown(idx).await?;
@@ -24,19 +125,86 @@ async fn update_data(data: &Value, api: &Api) -> Result<()> {
                     api.update_block(&markdown_data, idx).await?;
                 }
             }
+            "NodeHeading" => {
+                // 分隔线和前面的文本被siyuan解析为二级setext标题
+                if let Some(idx) = data["ID"].as_str().filter(|_| data["HeadingLevel"] == 2) {
+                    let markdown_data = api.get_block_kramdown(idx).await?;
+                    let setext = data["HeadingSetext"] == true;
+                    if let Some(markdown_data) = split_setext_heading(&markdown_data, setext) {
+                        api.update_block(&markdown_data, idx).await?;
+                    }
+                }
+            }
             "NodeBlockquote" => {
                 if let Some(idx) = data["ID"].as_str() {
+                    remove_blank_quote_lines(data, api).await?;
                     let markdown_data = api.get_block_kramdown(idx).await?;
                     let markdown_data = update_node_blockquote(&markdown_data)?;
+                    let markdown_data = trim_quote_lines(&markdown_data);
                     api.update_block(&markdown_data, idx).await?;
                 }
             }
-            _ => {
-                if let Some(children) = data.get("Children") {
-                    if let Some(children) = children.as_array() {
-                        for child in children {
-                            Box::pin(update_data(child, api)).await?;
+            "NodeCodeBlock" => {
+                if let Some(idx) = data["ID"].as_str() {
+                    let markdown_data = api.get_block_kramdown(idx).await?;
+                    let markdown_data = update_node_code_block(&markdown_data);
+                    api.update_block(&markdown_data, idx).await?;
+                }
+            }
+            "NodeHTMLBlock" => {
+                if let Some(idx) = data["ID"].as_str() {
+                    let markdown_data = api.get_block_kramdown(idx).await?;
+                    if let Some(markdown_data) = update_node_html_block(&markdown_data)? {
+                        api.update_block(&markdown_data, idx).await?;
+                    }
+                }
+            }
+            "NodeTable" => {
+                if let Some(idx) = data["ID"].as_str() {
+                    let markdown_data = api.get_block_kramdown(idx).await?;
+                    let markdown_data = update_node_table(&markdown_data);
+                    api.update_block(&markdown_data, idx).await?;
+                }
+            }
+            "NodeList" => {
+                if let Some(idx) = data["ID"].as_str() {
+                    let markdown_data = api.get_block_kramdown(idx).await?;
+                    // 静态的目录替换为嵌入块或者删除, 不再需要更新列表项
+                    let nav_data = update_nav_block(&markdown_data, config);
+                    if let Some(nav_data) = nav_data.filter(|nav| *nav != markdown_data) {
+                        if nav_data.is_empty() {
+                            api.delete_block(idx).await?;
+                        } else {
+                            api.update_block(&nav_data, idx).await?;
                         }
+                        return Ok(());
+                    }
+                    let markdown_data = repair_list_nesting(&update_node_list(&markdown_data));
+                    api.update_block(&markdown_data, idx).await?;
+                }
+                // 列表项中的段落等仍然需要更新
+                update_children(data, api, config).await?;
+            }
+            _ => update_children(data, api, config).await?,
+        }
+    }
+    Ok(())
+}
+
+async fn update_children(data: &Value, api: &Api, config: &Config) -> Result<()> {
+    if let Some(children) = data.get("Children") {
+        if let Some(children) = children.as_array() {
+            for (i, child) in children.iter().enumerate() {
+                Box::pin(update_data(child, api, config)).await?;
+                // notion的代码块caption在siyuan中是代码块后面的段落
+                let after_code = i > 0 && children[i - 1]["Type"] == "NodeCodeBlock";
+                if !after_code || child["Type"] != "NodeParagraph" {
+                    continue;
+                }
+                if let Some(idx) = child["ID"].as_str() {
+                    let markdown_data = api.get_block_kramdown(idx).await?;
+                    if let Some(markdown_data) = update_code_caption(&markdown_data) {
+                        api.update_block(&markdown_data, idx).await?;
                     }
                 }
             }
@@ -46,22 +214,37 @@ async fn update_data(data: &Value, api: &Api) -> Result<()> {
 }
 
 #[allow(dead_code)]
-pub(crate) async fn update_notebook(notebook_name: &str, base_url: Option<&str>) -> Result<()> {
+pub(crate) async fn update_notebook(
+    notebook_name: &str,
+    base_url: Option<&str>,
+    config: &Config,
+) -> Result<()> {
     let base_url = base_url.unwrap_or("http://127.0.0.1:6806");
     let mut api = Api::new(base_url);
     api.set_notebook_name(notebook_name).await?;
 
     let files = api.get_all_sy_files().await?;
-    for file in files {
-        let data = api.get_file(&file).await?;
+    for file in &files {
+        let data = api.get_file(file).await?;
         let data: Value = serde_json::from_str(&data)?;
-        update_data(&data, &api).await?
+        update_data(&data, &api, config).await?
+    }
+
+    // 所有文档更新之后再查找重复的同步块
+    if let Some(min_blocks) = config.min_duplicate_blocks {
+        let mut docs = Vec::new();
+        for file in &files {
+            let data = api.get_file(file).await?;
+            docs.push(serde_json::from_str(&data)?);
+        }
+        dedup_documents(&docs, &api, min_blocks).await?;
     }
     Ok(())
 }
 
 pub struct Notebook {
     api: Arc<Mutex<Api>>,
+    config: Arc<Mutex<Config>>,
 }
 
 /// 流程:
@@ -78,6 +261,7 @@ impl Notebook {
         let api = Api::new(base_url);
         Ok(Self {
             api: Arc::new(Mutex::new(api)),
+            config: Arc::new(Mutex::new(Config::default())),
         })
     }
 
@@ -96,7 +280,17 @@ impl Notebook {
         let api = Arc::clone(&self.api);
         rt.block_on(async {
             let mut api = api.lock().await;
-            api.notebook_name = Some(String::from(name));
+            api.set_notebook_name(name).await
+        })?;
+        Ok(())
+    }
+
+    pub fn set_config(&self, config: Config) -> Result<()> {
+        let rt = Runtime::new()?;
+        let current = Arc::clone(&self.config);
+        rt.block_on(async {
+            let mut current = current.lock().await;
+            *current = config;
         });
         Ok(())
     }
@@ -114,15 +308,64 @@ impl Notebook {
     pub fn process_file(&self, path: &str) -> Result<()> {
         let rt = Runtime::new()?;
         let api = Arc::clone(&self.api);
+        let config = Arc::clone(&self.config);
         rt.block_on(async {
             let api = api.lock().await;
+            let config = config.lock().await;
             let data = fs::read_to_string(path).await?;
             let data: Value = serde_json::from_str(&data)?;
-            update_data(&data, &api).await?;
+            update_data(&data, &api, &config).await?;
             Ok::<(), anyhow::Error>(())
         })?;
         Ok(())
     }
+
+    /// 在所有文件处理完之后, 把不同文件中重复的同步块替换为嵌入块, 返回替换的数量
+    pub fn dedup_files(&self, paths: Vec<String>) -> Result<usize> {
+        let rt = Runtime::new()?;
+        let api = Arc::clone(&self.api);
+        let config = Arc::clone(&self.config);
+        let count = rt.block_on(async {
+            let api = api.lock().await;
+            let config = config.lock().await;
+            let Some(min_blocks) = config.min_duplicate_blocks else {
+                return Ok(0);
+            };
+            let mut docs = Vec::new();
+            for path in &paths {
+                let data = fs::read_to_string(path).await?;
+                docs.push(serde_json::from_str(&data)?);
+            }
+            dedup_documents(&docs, &api, min_blocks).await
+        })?;
+        Ok(count)
+    }
+
+    /// 直接导入notion导出的zip文件, 返回创建的文档id
+    pub fn import_zip(&self, path: &str) -> Result<Vec<String>> {
+        let rt = Runtime::new()?;
+        let api = Arc::clone(&self.api);
+        let config = Arc::clone(&self.config);
+        let ids = rt.block_on(async {
+            let api = api.lock().await;
+            let config = config.lock().await;
+            import_zip(Path::new(path), &api, &config).await
+        })?;
+        Ok(ids)
+    }
+
+    /// 导入保存在本地的notion api json(`blocks.children.list`的结果), 返回创建的文档id
+    pub fn import_json_dir(&self, path: &str) -> Result<Vec<String>> {
+        let rt = Runtime::new()?;
+        let api = Arc::clone(&self.api);
+        let config = Arc::clone(&self.config);
+        let ids = rt.block_on(async {
+            let api = api.lock().await;
+            let config = config.lock().await;
+            import_json_dir(Path::new(path), &api, &config).await
+        })?;
+        Ok(ids)
+    }
 }
 
 #[cfg(test)]
@@ -131,7 +374,12 @@ mod tests {
 
     #[tokio::test]
     async fn test_update_notebook() {
-        let res = update_notebook("test-notion", Some("http://127.0.0.1:54113")).await;
+        let res = update_notebook(
+            "test-notion",
+            Some("http://127.0.0.1:54113"),
+            &Config::default(),
+        )
+        .await;
         println!("{:?}", res);
     }
 }
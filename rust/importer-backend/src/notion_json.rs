@@ -0,0 +1,630 @@
+use crate::api::Api;
+use crate::attachment::update_document_attachments;
+use crate::block::normalize_code_language;
+use crate::config::Config;
+use crate::heading::normalize_headings;
+use crate::html::prefix_lines;
+use crate::image::localize_images;
+use crate::markdown::transform_markdown;
+use crate::mention::update_page_mentions;
+use crate::meta::{parse_notion_time, set_page_meta, timestamp_id, PageMeta};
+use anyhow::Result;
+use chrono::Local;
+use glob::Pattern;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// notion api导出的页面, 每个json文件是一个页面的`blocks.children.list`结果
+///
+/// 文件名是页面的id, 例如: `59833787-2cf9-4fdf-8782-e53db20768a5.json`, 支持以下几种格式:
+///
+/// ```json
+/// {"object": "list", "results": [...], "next_cursor": null, "has_more": false}
+/// [{"object": "list", "results": [...]}, {"object": "list", "results": [...]}]
+/// ```
+///
+/// 子块可以内嵌在块的`children`字段中, 也可以保存为以子块id命名的单独的json文件,
+/// 页面的`title`, `icon`, `cover`, `created_time`和`last_edited_time`可以和`results`保存在一起,
+/// 没有`title`时使用`properties`中标题类型的属性
+#[derive(Debug)]
+pub(crate) struct JsonPage {
+    pub(crate) id: String,
+    pub(crate) title: Option<String>,
+    pub(crate) blocks: Vec<Value>,
+    pub(crate) meta: PageMeta,
+}
+
+/// 从磁盘读取notion api导出的json并创建文档, 返回创建的文档id
+pub(crate) async fn import_json_dir(dir: &Path, api: &Api, config: &Config) -> Result<Vec<String>> {
+    let notebook = api.get_notebook_id()?;
+    let pages = load_pages(dir)?;
+    let hpaths = page_hpaths(&pages);
+    // 先记录所有页面的文档id, 页面提及可以引用后面的页面
+    let mut doc_ids = Vec::new();
+    for (hpath, page) in &hpaths {
+        let time = match page.meta.created {
+            Some(created) if config.timestamp_ids => created,
+            _ => Local::now().naive_local(),
+        };
+        let id = timestamp_id(time, hpath);
+        if let Some(title) = hpath.rsplit('/').next() {
+            api.add_doc_title(title, &id).await;
+        }
+        doc_ids.push(id);
+    }
+    let mut ids = Vec::new();
+    for ((hpath, page), id) in hpaths.into_iter().zip(doc_ids) {
+        let markdown = blocks_to_kramdown(&page.blocks, &pages);
+        let markdown = normalize_headings(&markdown, hpath.rsplit('/').next(), config);
+        let mut markdown = transform_markdown(&markdown, config)?;
+        // api返回的文件链接会过期
+        if let Some(cache_dir) = &config.image_cache_dir {
+            markdown = localize_images(&markdown, api, cache_dir).await?;
+        }
+        let markdown = update_document_attachments(&markdown);
+        let markdown = update_page_mentions(&markdown, api).await?;
+        let id = api
+            .create_doc_with_md(&notebook, &hpath, &markdown, Some(&id))
+            .await?;
+        set_page_meta(api, &id, &page.meta, Some(dir)).await?;
+        ids.push(id);
+    }
+    Ok(ids)
+}
+
+/// 子块的最大嵌套深度, 超过时不再读取子块
+const MAX_DEPTH: usize = 32;
+
+/// 读取目录中的所有json文件, 返回页面id到页面的映射
+pub(crate) fn load_pages(dir: &Path) -> Result<HashMap<String, JsonPage>> {
+    let pattern = format!("{}/**/*.json", Pattern::escape(&dir.to_string_lossy()));
+    let mut pages = HashMap::new();
+    for file in glob::glob(&pattern)?.flatten() {
+        let Some(stem) = file.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let data: Value = serde_json::from_str(&fs::read_to_string(&file)?)?;
+        let lists = match &data {
+            Value::Array(lists) => lists.clone(),
+            _ => vec![data.clone()],
+        };
+        let blocks = lists
+            .iter()
+            .flat_map(|list| list["results"].as_array().cloned().unwrap_or_default())
+            .collect::<Vec<_>>();
+        let title = data["title"]
+            .as_str()
+            .map(String::from)
+            .or_else(|| page_title(&data["properties"]))
+            .filter(|title| !title.trim().is_empty());
+        let meta = PageMeta {
+            icon: data["icon"]["emoji"].as_str().map(String::from),
+            cover: Some(file_url(&data["cover"])).filter(|url| !url.is_empty()),
+            created: data["created_time"].as_str().and_then(parse_notion_time),
+            updated: data["last_edited_time"]
+                .as_str()
+                .and_then(parse_notion_time),
+        };
+        let id = normalize_id(stem);
+        pages.insert(
+            id.clone(),
+            JsonPage {
+                id,
+                title,
+                blocks,
+                meta,
+            },
+        );
+    }
+    Ok(pages)
+}
+
+/// 根据`child_page`块计算每个页面在siyuan中的路径, 父页面排在子页面前面
+///
+/// 没有被其他页面引用的是根页面, 子块单独保存的json文件不是页面, 没有标题的根页面使用`Untitled`
+pub(crate) fn page_hpaths(pages: &HashMap<String, JsonPage>) -> Vec<(String, &JsonPage)> {
+    // 子页面id => (父页面id, 标题)
+    let mut parents = HashMap::new();
+    let mut referenced = HashSet::new();
+    for page in pages.values() {
+        for block in &page.blocks {
+            let id = normalize_id(block["id"].as_str().unwrap_or(""));
+            if block["type"] == "child_page" {
+                let title = block["child_page"]["title"].as_str().unwrap_or("Untitled");
+                parents.insert(id, (page.id.clone(), title.to_string()));
+            } else {
+                referenced.insert(id);
+            }
+        }
+    }
+
+    let mut res = Vec::new();
+    for page in pages.values() {
+        if referenced.contains(&page.id) {
+            continue;
+        }
+        let mut parts = Vec::new();
+        let mut current = page.id.clone();
+        while let Some((parent, title)) = parents.get(&current) {
+            parts.push(title.clone());
+            if parts.len() > pages.len() {
+                break;
+            }
+            current = parent.clone();
+        }
+        let root = pages.get(&current).and_then(|root| root.title.clone());
+        parts.push(root.unwrap_or_else(|| "Untitled".to_string()));
+        parts.reverse();
+        res.push((format!("/{}", parts.join("/")), page));
+    }
+    res.sort_by(|(a, _), (b, _)| {
+        let depth = |hpath: &str| hpath.matches('/').count();
+        depth(a).cmp(&depth(b)).then(a.cmp(b))
+    });
+    res
+}
+
+/// 把notion的块列表转换为siyuan的kramdown
+pub(crate) fn blocks_to_kramdown(blocks: &[Value], pages: &HashMap<String, JsonPage>) -> String {
+    convert_blocks(blocks, pages, &[])
+}
+
+/// `ancestors`是上层块的id, 用来避免同步块互相引用时无限递归
+fn convert_blocks(
+    blocks: &[Value],
+    pages: &HashMap<String, JsonPage>,
+    ancestors: &[String],
+) -> String {
+    let mut res: Vec<(&str, String)> = Vec::new();
+    let mut number = 0;
+    for block in blocks {
+        let block_type = block["type"].as_str().unwrap_or("");
+        number = if block_type == "numbered_list_item" {
+            number + 1
+        } else {
+            0
+        };
+        let kramdown = block_to_kramdown(block, number, pages, ancestors);
+        if kramdown.is_empty() {
+            continue;
+        }
+        // 相邻的列表项属于同一个列表
+        let is_item = |t: &str| t.ends_with("list_item") || t == "to_do" || t == "toggle";
+        match res.last_mut() {
+            Some((last_type, last)) if is_item(last_type) && *last_type == block_type => {
+                *last = format!("{}\n{}", last, kramdown);
+            }
+            _ => res.push((block_type, kramdown)),
+        }
+    }
+    res.into_iter()
+        .map(|(_, kramdown)| kramdown)
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// 转换单个notion块, `number`是有序列表项的序号
+fn block_to_kramdown(
+    block: &Value,
+    number: usize,
+    pages: &HashMap<String, JsonPage>,
+    ancestors: &[String],
+) -> String {
+    let block_type = block["type"].as_str().unwrap_or("");
+    let content = &block[block_type];
+    let text = rich_text_to_kramdown(&content["rich_text"]);
+    let children = || match block_children(block, pages, ancestors) {
+        Some((id, children)) => {
+            let mut path = ancestors.to_vec();
+            path.push(id);
+            convert_blocks(&children, pages, &path)
+        }
+        None => String::new(),
+    };
+
+    match block_type {
+        "paragraph" => with_children(&text, &children(), ""),
+        "heading_1" | "heading_2" | "heading_3" => {
+            let level = block_type[8..].parse::<usize>().unwrap_or(1);
+            let heading = format!("{} {}", "#".repeat(level), text);
+            if content["is_toggleable"].as_bool().unwrap_or(false) {
+                with_children(&format!("{}\n{{: fold=\"1\"}}", heading), &children(), "")
+            } else {
+                heading
+            }
+        }
+        "bulleted_list_item" => with_children(&format!("* {}", text), &children(), "  "),
+        "numbered_list_item" => {
+            let marker = format!("{}. ", number);
+            let indent = " ".repeat(marker.len());
+            with_children(&format!("{}{}", marker, text), &children(), &indent)
+        }
+        "to_do" => {
+            let checked = content["checked"].as_bool().unwrap_or(false);
+            let item = format!("* [{}] {}", if checked { "X" } else { " " }, text);
+            with_children(&item, &children(), "  ")
+        }
+        "toggle" => with_children(&format!("* {{: fold=\"1\"}}{}", text), &children(), "  "),
+        "code" => {
+            let language = normalize_code_language(content["language"].as_str().unwrap_or(""));
+            let code = plain_text(&content["rich_text"]);
+            let caption = rich_text_to_kramdown(&content["caption"]);
+            let code = format!("```{}\n{}\n```", language, code);
+            with_children(&code, &caption, "")
+        }
+        "equation" => {
+            let expression = content["expression"].as_str().unwrap_or("");
+            format!("$$\n{}\n$$", expression.trim())
+        }
+        "callout" => {
+            let icon = content["icon"]["emoji"].as_str().unwrap_or("");
+            let text = format!("{} {}", icon, text);
+            let quote = with_children(text.trim(), &children(), "");
+            prefix_lines(&quote, "> ", ">")
+        }
+        "quote" => prefix_lines(&with_children(&text, &children(), ""), "> ", ">"),
+        "divider" => "---".to_string(),
+        "image" => {
+            let url = file_url(content);
+            let caption = escape_markdown(&plain_text(&content["caption"]));
+            format!("![{}]({})", caption, url)
+        }
+        "bookmark" | "embed" | "link_preview" | "video" | "pdf" | "file" | "audio" => {
+            let url = match content["url"].as_str() {
+                Some(url) => url.to_string(),
+                None => file_url(content),
+            };
+            let caption = escape_markdown(&plain_text(&content["caption"]));
+            let title = if caption.is_empty() { &url } else { &caption };
+            format!("[{}]({})", title, url)
+        }
+        "table" => table_to_kramdown(block, pages, ancestors),
+        // 子页面会作为子文档创建
+        "child_page" => String::new(),
+        "child_database" => {
+            let title = content["title"].as_str().unwrap_or("");
+            format!("**{}**", title)
+        }
+        "synced_block" | "column_list" | "column" => children(),
+        // 由`update_nav_block`替换为嵌入块或者删除
+        "table_of_contents" | "breadcrumb" => {
+            format!("{{: custom-notion-block=\"{}\"}}", block_type)
+        }
+        _ => text,
+    }
+}
+
+/// 读取块的子块, 返回(子块所属的块id, 子块), 内嵌的`children`优先, 否则从单独的json文件读取
+///
+/// 子块所属的块已经是上层块或者嵌套太深时返回`None`
+fn block_children(
+    block: &Value,
+    pages: &HashMap<String, JsonPage>,
+    ancestors: &[String],
+) -> Option<(String, Vec<Value>)> {
+    let block_type = block["type"].as_str().unwrap_or("");
+    // 同步块的副本从原始块读取子块
+    let id = block[block_type]["synced_from"]["block_id"]
+        .as_str()
+        .or(block["id"].as_str())
+        .map(normalize_id)
+        .unwrap_or_default();
+    if ancestors.len() >= MAX_DEPTH || (!id.is_empty() && ancestors.contains(&id)) {
+        return None;
+    }
+    if let Some(children) = block["children"].as_array() {
+        return Some((id, children.clone()));
+    }
+    if let Some(children) = block[block_type]["children"].as_array() {
+        return Some((id, children.clone()));
+    }
+    if !block["has_children"].as_bool().unwrap_or(false) {
+        return None;
+    }
+    let children = pages.get(&id).map(|page| page.blocks.clone())?;
+    Some((id, children))
+}
+
+/// 转换表格, `has_column_header`为false时使用空的表头
+fn table_to_kramdown(
+    block: &Value,
+    pages: &HashMap<String, JsonPage>,
+    ancestors: &[String],
+) -> String {
+    let rows = block_children(block, pages, ancestors)
+        .map(|(_, rows)| rows)
+        .unwrap_or_default()
+        .iter()
+        .map(|row| {
+            row["table_row"]["cells"]
+                .as_array()
+                .map(|cells| {
+                    cells
+                        .iter()
+                        .map(|cell| escape_table_pipes(&rich_text_to_kramdown(cell)))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    let columns = block["table"]["table_width"]
+        .as_u64()
+        .map(|width| width as usize)
+        .unwrap_or_else(|| rows.iter().map(|row| row.len()).max().unwrap_or(0));
+    let mut rows = rows.into_iter();
+    let header = if block["table"]["has_column_header"]
+        .as_bool()
+        .unwrap_or(false)
+    {
+        rows.next().unwrap_or_default()
+    } else {
+        vec![]
+    };
+
+    // siyuan没有标题列, 标题列的单元格加粗
+    let row_header = block["table"]["has_row_header"].as_bool().unwrap_or(false);
+    let rows = rows.map(|mut row| {
+        match row.first_mut() {
+            Some(cell) if row_header && !cell.trim().is_empty() => {
+                *cell = format!("**{}**", cell.trim());
+            }
+            _ => {}
+        }
+        row
+    });
+
+    let format_row = |mut row: Vec<String>| {
+        row.resize(columns, String::new());
+        format!("| {} |", row.join(" | "))
+    };
+    let mut lines = vec![format_row(header), format!("|{}", " --- |".repeat(columns))];
+    lines.extend(rows.map(format_row));
+    lines.join("\n")
+}
+
+/// 把notion的rich text数组转换为行内kramdown
+pub(crate) fn rich_text_to_kramdown(rich_text: &Value) -> String {
+    let Some(items) = rich_text.as_array() else {
+        return String::new();
+    };
+    items.iter().map(rich_text_item_to_kramdown).collect()
+}
+
+fn rich_text_item_to_kramdown(item: &Value) -> String {
+    let plain = item["plain_text"].as_str().unwrap_or("");
+    if item["type"] == "equation" {
+        let expression = item["equation"]["expression"].as_str().unwrap_or(plain);
+        return format!("${}$", expression.trim());
+    }
+
+    let annotations = &item["annotations"];
+    let annotated = |name: &str| annotations[name].as_bool().unwrap_or(false);
+    // 首尾的空白放在标记外面, 否则`** bold**`不会被当作加粗
+    let text = plain.trim();
+    if text.is_empty() {
+        return plain.to_string();
+    }
+    let start = &plain[..plain.len() - plain.trim_start().len()];
+    let end = &plain[plain.trim_end().len()..];
+    let mut text = if annotated("code") {
+        format!("`{}`", text)
+    } else {
+        escape_markdown(text)
+    };
+    if annotated("bold") {
+        text = format!("**{}**", text);
+    }
+    if annotated("italic") {
+        text = format!("*{}*", text);
+    }
+    if annotated("strikethrough") {
+        text = format!("~~{}~~", text);
+    }
+    if annotated("underline") {
+        text = format!("<u>{}</u>", text);
+    }
+    // 颜色由`update_notion_colors`转换为样式
+    match annotations["color"].as_str() {
+        Some(color) if color != "default" => {
+            text = format!(r#"<span class="highlight-{}">{}</span>"#, color, text);
+        }
+        _ => {}
+    }
+    match item["href"].as_str() {
+        Some(href) => format!("{}[{}]({}){}", start, text, href, end),
+        None => format!("{}{}{}", start, text, end),
+    }
+}
+
+/// 页面对象`properties`中标题类型的属性
+fn page_title(properties: &Value) -> Option<String> {
+    properties
+        .as_object()?
+        .values()
+        .find(|property| property["type"] == "title")
+        .map(|property| plain_text(&property["title"]))
+}
+
+/// 转义文本中的markdown标记, 避免`*`、`#`等字符和开头的列表标记被当作格式
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '#' | '[' | ']' | '`' | '|') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    // 开头的`1.`和`-`会被当作列表
+    let digits = escaped.len()
+        - escaped
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .len();
+    if digits > 0 && escaped[digits..].starts_with('.') {
+        escaped.insert(digits, '\\');
+    } else if escaped.starts_with('-') || escaped.starts_with('+') {
+        escaped.insert(0, '\\');
+    }
+    escaped
+}
+
+/// 转义单元格中未转义的`|`, 代码中的`|`也需要转义
+fn escape_table_pipes(cell: &str) -> String {
+    let mut escaped = String::with_capacity(cell.len());
+    let mut chars = cell.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                escaped.push(c);
+                escaped.extend(chars.next());
+            }
+            '|' => escaped.push_str("\\|"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn plain_text(rich_text: &Value) -> String {
+    rich_text
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item["plain_text"].as_str())
+                .collect::<String>()
+        })
+        .unwrap_or_default()
+}
+
+/// notion的文件对象, 可能是`file`(notion托管)或者`external`
+fn file_url(content: &Value) -> String {
+    content["file"]["url"]
+        .as_str()
+        .or(content["external"]["url"].as_str())
+        .unwrap_or("")
+        .to_string()
+}
+
+/// 把子块放在父块后面, `indent`是子块的缩进
+fn with_children(parent: &str, children: &str, indent: &str) -> String {
+    if children.is_empty() {
+        parent.to_string()
+    } else {
+        format!("{}\n\n{}", parent, prefix_lines(children, indent, ""))
+    }
+}
+
+/// 去掉页面id中的`-`, 统一为小写
+fn normalize_id(id: &str) -> String {
+    id.replace('-', "").to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_blocks_to_kramdown() {
+        let blocks = json!([
+            {"id": "1", "type": "heading_1", "heading_1": {"rich_text": [{"type": "text", "plain_text": "Title", "annotations": {}}]}},
+            {"id": "2", "type": "paragraph", "paragraph": {"rich_text": [
+                {"type": "text", "plain_text": "bold", "annotations": {"bold": true, "color": "default"}},
+                {"type": "text", "plain_text": " and ", "annotations": {}},
+                {"type": "equation", "plain_text": "x^2", "equation": {"expression": "x^2"}},
+                {"type": "text", "plain_text": " red", "annotations": {"color": "red"}, "href": "https://example.com"}
+            ]}},
+            {"id": "3", "type": "numbered_list_item", "numbered_list_item": {"rich_text": [{"plain_text": "first"}]}},
+            {"id": "4", "type": "numbered_list_item", "numbered_list_item": {"rich_text": [{"plain_text": "second"}]},
+                "has_children": true, "children": [
+                    {"id": "5", "type": "to_do", "to_do": {"rich_text": [{"plain_text": "done"}], "checked": true}}
+                ]},
+            {"id": "6", "type": "code", "code": {"language": "C++", "rich_text": [{"plain_text": "int a;"}], "caption": []}},
+            {"id": "7", "type": "child_page", "child_page": {"title": "Child"}},
+            {"id": "8", "type": "table", "table": {"table_width": 2, "has_column_header": true}, "has_children": true, "children": [
+                {"type": "table_row", "table_row": {"cells": [[{"plain_text": "A"}], [{"plain_text": "B"}]]}},
+                {"type": "table_row", "table_row": {"cells": [[{"plain_text": "1"}], [{"plain_text": "a|b"}]]}}
+            ]}
+        ]);
+        let target = r#"# Title
+
+**bold** and $x^2$ [<span class="highlight-red">red</span>](https://example.com)
+
+1. first
+2. second
+
+   * [X] done
+
+```cpp
+int a;
+```
+
+| A | B |
+| --- | --- |
+| 1 | a\|b |"#;
+        let kramdown = blocks_to_kramdown(blocks.as_array().unwrap(), &HashMap::new());
+        assert_eq!(kramdown, target);
+    }
+
+    #[test]
+    fn test_rich_text_edge_spaces() {
+        let rich_text = json!([
+            {"plain_text": "bold ", "annotations": {"bold": true, "italic": true}},
+            {"plain_text": "text"}
+        ]);
+        assert_eq!(rich_text_to_kramdown(&rich_text), "***bold*** text");
+        // markdown标记按原文显示
+        let rich_text = json!([{"plain_text": "a * b * c"}]);
+        assert_eq!(rich_text_to_kramdown(&rich_text), r"a \* b \* c");
+        let rich_text = json!([{"plain_text": "# not heading"}]);
+        assert_eq!(rich_text_to_kramdown(&rich_text), r"\# not heading");
+        let rich_text = json!([{"plain_text": "1. not list"}, {"plain_text": "a_b", "annotations": {"code": true}}]);
+        assert_eq!(rich_text_to_kramdown(&rich_text), r"1\. not list`a_b`");
+        // 同步块引用自己时不会无限递归
+        let mut pages = HashMap::new();
+        let synced = json!({"id": "cccc-0001", "type": "synced_block", "has_children": true,
+            "synced_block": {"synced_from": {"block_id": "cccc-0001"}}});
+        pages.insert(
+            "cccc0001".to_string(),
+            JsonPage {
+                id: "cccc0001".to_string(),
+                title: None,
+                blocks: vec![synced.clone()],
+                meta: PageMeta::default(),
+            },
+        );
+        assert_eq!(blocks_to_kramdown(&[synced], &pages), "");
+    }
+
+    #[test]
+    fn test_page_hpaths() {
+        let mut pages = HashMap::new();
+        let child_block =
+            json!({"id": "bbbb-0001", "type": "child_page", "child_page": {"title": "Child"}});
+        pages.insert(
+            "aaaa0001".to_string(),
+            JsonPage {
+                id: "aaaa0001".to_string(),
+                title: Some("Root".to_string()),
+                blocks: vec![child_block],
+                meta: PageMeta::default(),
+            },
+        );
+        pages.insert(
+            "bbbb0001".to_string(),
+            JsonPage {
+                id: "bbbb0001".to_string(),
+                title: None,
+                blocks: vec![],
+                meta: PageMeta::default(),
+            },
+        );
+        let hpaths = page_hpaths(&pages)
+            .into_iter()
+            .map(|(hpath, _)| hpath)
+            .collect::<Vec<_>>();
+        assert_eq!(hpaths, vec!["/Root", "/Root/Child"]);
+    }
+}
@@ -0,0 +1,122 @@
+use fancy_regex::Regex;
+
+/// 更新表格
+///
+/// - notion没有标题行的表格会导出一个空的标题行, 或者把第一行重复作为标题行, 这里用第一行作为标题行
+/// - 单元格中的各种`<br>`统一为siyuan的`<br />`, 代码中的`|`转义为`\|`
+/// - 每行补齐到相同的列数, 保留导出的对齐方式
+pub(crate) fn update_node_table(data: &str) -> String {
+    // 移除最后一行
+    let lines = data.lines().collect::<Vec<_>>();
+    let lines = &lines[..lines.len().saturating_sub(1)];
+    let Some(separator) = lines.iter().position(|line| is_separator(line)) else {
+        return lines.join("\n");
+    };
+    if separator != 1 {
+        return lines.join("\n");
+    }
+
+    let mut header = split_row(lines[0]);
+    let mut aligns = split_row(lines[1]);
+    let mut rows = lines[2..]
+        .iter()
+        .map(|line| split_row(line))
+        .collect::<Vec<_>>();
+    if !rows.is_empty() && header.iter().all(|cell| cell.is_empty()) {
+        header = rows.remove(0);
+    } else if rows.first() == Some(&header) {
+        rows.remove(0);
+    }
+
+    let columns = rows
+        .iter()
+        .map(|row| row.len())
+        .chain([header.len()])
+        .max()
+        .unwrap_or(0);
+    header.resize(columns, String::new());
+    aligns.resize(columns, "---".to_string());
+    for row in rows.iter_mut() {
+        row.resize(columns, String::new());
+    }
+    for align in aligns.iter_mut() {
+        if !align.starts_with(':') && !align.ends_with(':') {
+            *align = "---".to_string();
+        }
+    }
+
+    let format_row = |row: &[String]| format!("| {} |", row.join(" | "));
+    let mut res = vec![format_row(&header), format_row(&aligns)];
+    res.extend(rows.iter().map(|row| format_row(row)));
+    res.join("\n")
+}
+
+/// 是否是表格的分隔行, 例如: `| --- | :---: |`
+fn is_separator(line: &str) -> bool {
+    let re = Regex::new(r"^\s*\|?\s*:?-+:?\s*(\|\s*:?-+:?\s*)*\|?\s*$").unwrap();
+    re.is_match(line).unwrap_or(false)
+}
+
+/// 拆分表格的一行, 已经转义的`\|`和代码中的`|`不作为分隔符
+fn split_row(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = match line.strip_suffix('|') {
+        Some(rest) if !rest.ends_with('\\') => rest,
+        _ => line,
+    };
+
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut in_code = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                cell.push(c);
+                if let Some(next) = chars.next() {
+                    cell.push(next);
+                }
+            }
+            '`' => {
+                in_code = !in_code;
+                cell.push(c);
+            }
+            '|' if in_code => cell.push_str("\\|"),
+            '|' => cells.push(std::mem::take(&mut cell)),
+            _ => cell.push(c),
+        }
+    }
+    cells.push(cell);
+    cells.iter().map(|cell| clean_cell(cell)).collect()
+}
+
+/// 单元格中的换行统一为`<br />`
+fn clean_cell(cell: &str) -> String {
+    let re = Regex::new(r"(?i)\s*<br\s*/?>\s*").unwrap();
+    re.replace_all(cell.trim(), "<br />").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_node_table() {
+        let data = r#"|  |  |  |
+| --- | --- | --- |
+| Name | Count | Note |
+| `a|b` | 1,024 | line 1<br>line 2 |
+| c | 5 |
+{: id="xxx"}"#;
+        let target = r#"| Name | Count | Note |
+| --- | --- | --- |
+| `a\|b` | 1,024 | line 1<br />line 2 |
+| c | 5 |  |"#;
+        assert_eq!(update_node_table(data), target);
+
+        let data = "| A | B |\n| :-: | --- |\n| A | B |\n| 1 | x |\n{: }";
+        let target = "| A | B |\n| :-: | --- |\n| 1 | x |";
+        assert_eq!(update_node_table(data), target);
+    }
+}
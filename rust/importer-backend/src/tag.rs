@@ -0,0 +1,147 @@
+use crate::api::Api;
+use anyhow::Result;
+use fancy_regex::Regex;
+use std::collections::HashMap;
+
+/// 从页面开头的属性中读取标签, 例如: `Tags: a, b` => `["a", "b"]`
+pub(crate) fn property_tags(data: &str, property: &str) -> Vec<String> {
+    property_value(data, property)
+        .map(split_tags)
+        .unwrap_or_default()
+}
+
+/// 读取页面开头的属性, 例如: `Created time: March 3, 2024 3:04 PM`
+///
+/// notion导出的markdown会把页面属性作为`名称: 值`放在标题后面, 遇到其他块就结束
+pub(crate) fn property_value<'a>(data: &'a str, property: &str) -> Option<&'a str> {
+    let prefix = format!("{}:", property);
+    for line in data.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with("# ") || line.starts_with("{:") {
+            continue;
+        }
+        if let Some(value) = line.strip_prefix(&prefix) {
+            return Some(value.trim());
+        }
+        if !is_property_line(line) {
+            break;
+        }
+    }
+    None
+}
+
+/// 把属性中的标签替换为siyuan的行内标签, 例如: `Tags: a, b` => `Tags: #a# #b#`
+pub(crate) fn inline_property_tags(data: &str, property: &str) -> String {
+    let re = Regex::new(&format!(
+        r"(?m)^({}:\s*)(.+)$",
+        fancy_regex::escape(property)
+    ))
+    .unwrap();
+    let Ok(Some(cap)) = re.captures(data) else {
+        return data.to_string();
+    };
+    let tags = split_tags(&cap[2]);
+    if tags.is_empty() || cap[2].trim().starts_with('#') {
+        return data.to_string();
+    }
+    let line = format!("{}{}", &cap[1], inline_tags(&tags));
+    data.replacen(&cap[0], &line, 1)
+}
+
+/// 读取数据库csv中每一行的标签, 返回(行的名称, 标签), 第一列是行的名称
+pub(crate) fn csv_tags(data: &str, property: &str) -> Result<HashMap<String, Vec<String>>> {
+    let tags = csv_column(data, property)?
+        .into_iter()
+        .map(|(name, value)| (name, split_tags(&value)))
+        .filter(|(_, tags)| !tags.is_empty())
+        .collect();
+    Ok(tags)
+}
+
+/// 读取数据库csv中每一行的某一列, 返回(行的名称, 值), 第一列是行的名称
+pub(crate) fn csv_column(data: &str, property: &str) -> Result<HashMap<String, String>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(data.as_bytes());
+    let Some(column) = reader.headers()?.iter().position(|h| h.trim() == property) else {
+        return Ok(HashMap::new());
+    };
+    let mut values = HashMap::new();
+    for record in reader.records() {
+        let record = record?;
+        let (Some(name), Some(value)) = (record.get(0), record.get(column)) else {
+            continue;
+        };
+        if !name.trim().is_empty() && !value.trim().is_empty() {
+            values.insert(name.trim().to_string(), value.trim().to_string());
+        }
+    }
+    Ok(values)
+}
+
+/// 把标签写入文档的`tags`属性
+pub(crate) async fn set_doc_tags(api: &Api, idx: &str, tags: &[String]) -> Result<()> {
+    if tags.is_empty() {
+        return Ok(());
+    }
+    let attrs = HashMap::from([("tags".to_string(), tags.join(","))]);
+    api.set_block_attrs(idx, &attrs).await
+}
+
+/// 在文档已有的`tags`属性后面加上新的标签, 已有的标签不重复添加
+pub(crate) fn merge_tags(existing: &str, tags: &[String]) -> Vec<String> {
+    let mut merged = split_tags(existing);
+    for tag in tags {
+        if !merged.contains(tag) {
+            merged.push(tag.clone());
+        }
+    }
+    merged
+}
+
+/// siyuan的行内标签, 例如: `#a# #b#`
+pub(crate) fn inline_tags(tags: &[String]) -> String {
+    tags.iter()
+        .map(|tag| format!("#{}#", tag))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// notion的多选属性用`,`分隔, siyuan的标签中不能有`#`和`,`
+fn split_tags(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|tag| tag.trim().trim_matches('#').trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+fn is_property_line(line: &str) -> bool {
+    let re = Regex::new(r"^[^:#>*`|\-\[][^:]{0,40}:\s").unwrap();
+    re.is_match(line).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_property_tags() {
+        let data = "# Page\n\nCreated: March 3, 2024\nTags: rust, notes\n\nSome text";
+        assert_eq!(property_tags(data, "Tags"), vec!["rust", "notes"]);
+        assert!(property_tags("Some text\n\nTags: a", "Tags").is_empty());
+        assert_eq!(
+            inline_property_tags(data, "Tags"),
+            "# Page\n\nCreated: March 3, 2024\nTags: #rust# #notes#\n\nSome text"
+        );
+    }
+
+    #[test]
+    fn test_csv_tags() {
+        let data = "Name,Tags\nFirst,\"a, b\"\nSecond,\n";
+        let tags = csv_tags(data, "Tags").unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags["First"], vec!["a", "b"]);
+        let tags = vec!["b".to_string(), "c".to_string()];
+        assert_eq!(merge_tags("a,b", &tags), vec!["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::config::{Config, NavBlockMode};
+use fancy_regex::Regex;
+
+/// 按照文档大纲的顺序嵌入当前文档的所有标题, 标题变化时会自动更新
+pub(crate) const HEADINGS_EMBED: &str = "{{//!js const flat = (items) => items.flatMap((item) => [item.id, ...flat(item.blocks || [])]); return fetchSyncPost('/api/outline/getDocOutline', {id: protyle.block.rootID}).then((res) => flat(res.data || []));}}";
+
+/// 转换notion的目录(`table_of_contents`)和面包屑(`breadcrumb`), 不是这两种块时返回`None`
+///
+/// 转换后的html和json中这两种块带有`custom-notion-block`属性, markdown导出的目录是只有指向notion块id的页内链接的列表,
+/// 返回空字符串表示删除这个块, `Static`返回去掉标记之后的块
+pub(crate) fn update_nav_block(data: &str, config: &Config) -> Option<String> {
+    let mode = match nav_block_type(data)? {
+        "breadcrumb" => &config.breadcrumb,
+        _ => &config.table_of_contents,
+    };
+    match mode {
+        NavBlockMode::Static => Some(strip_marker(data)),
+        NavBlockMode::Embed if nav_block_type(data) == Some("table_of_contents") => {
+            Some(HEADINGS_EMBED.to_string())
+        }
+        // 面包屑在siyuan中由编辑器显示, 没有对应的嵌入块
+        NavBlockMode::Embed | NavBlockMode::Remove => Some(String::new()),
+    }
+}
+
+/// 读取块的类型, 静态的目录是每一项都是指向notion块id的页内链接(`[title](#1a2b...)`)的列表,
+/// 手写的页内链接(`[Intro](#intro)`)不是目录
+fn nav_block_type(data: &str) -> Option<&'static str> {
+    if data.contains(r#"custom-notion-block="table_of_contents""#) {
+        return Some("table_of_contents");
+    }
+    if data.contains(r#"custom-notion-block="breadcrumb""#) {
+        return Some("breadcrumb");
+    }
+    let item_re = Regex::new(
+        r"^\s*(?:[*+-]|\d+[.)])\s+(?:\{:[^}]*\})?\[[^\]]*\]\(#(?:[0-9a-f]{32}|[0-9a-f]{8}(?:-[0-9a-f]{4}){3}-[0-9a-f]{12})\)\s*$",
+    )
+    .unwrap();
+    let lines = data
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with("{:"))
+        .collect::<Vec<_>>();
+    if !lines.is_empty()
+        && lines
+            .iter()
+            .all(|line| item_re.is_match(line).unwrap_or(false))
+    {
+        Some("table_of_contents")
+    } else {
+        None
+    }
+}
+
+fn strip_marker(data: &str) -> String {
+    let re = Regex::new(r#"\n?\{:\s*custom-notion-block="[a-z_]+"\s*\}"#).unwrap();
+    re.replace_all(data, "").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_nav_block() {
+        let mut config = Config::default();
+        let toc = "* [Intro](#0123456789abcdef0123456789abcdef)\n  * [Usage](#01234567-89ab-cdef-0123-456789abcdef)";
+        assert_eq!(update_nav_block(toc, &config).unwrap(), HEADINGS_EMBED);
+        assert!(update_nav_block("* [Intro](#intro)\n* [Usage](#usage)", &config).is_none());
+        let breadcrumb = r#"{: custom-notion-block="breadcrumb"}"#;
+        assert_eq!(update_nav_block(breadcrumb, &config).unwrap(), "");
+        assert!(update_nav_block("* [Site](https://example.com)", &config).is_none());
+
+        config.table_of_contents = NavBlockMode::Static;
+        let toc = "* Intro\n{: custom-notion-block=\"table_of_contents\"}";
+        assert_eq!(update_nav_block(toc, &config).unwrap(), "* Intro");
+    }
+}
@@ -0,0 +1,173 @@
+use crate::error::MyResult;
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// 和`importer_backend`中的枚举相同, uniffi只能为本crate的类型生成绑定
+pub enum NavBlockMode {
+    Static,
+    Embed,
+    Remove,
+}
+
+pub enum ChildLinkMode {
+    Keep,
+    Remove,
+    Ref,
+}
+
+/// udl的枚举需要命名的字段
+pub enum HeadingLevelMode {
+    Keep,
+    Shift { levels: i32 },
+    Compress,
+}
+
+/// `importer_backend::Config`在ffi中的表示, 路径使用字符串, 日期使用`YYYY-MM-DD`格式
+pub struct Config {
+    pub export_dir: Option<String>,
+    pub colors: HashMap<String, String>,
+    pub embed_providers: Vec<String>,
+    pub min_duplicate_blocks: Option<u64>,
+    pub export_date: Option<String>,
+    pub date_mention_mark: bool,
+    pub user_mention_format: String,
+    pub tag_property: String,
+    pub inline_tags: bool,
+    pub created_property: String,
+    pub updated_property: String,
+    pub timestamp_ids: bool,
+    pub footnotes: bool,
+    pub table_of_contents: NavBlockMode,
+    pub breadcrumb: NavBlockMode,
+    pub child_page_links: ChildLinkMode,
+    pub remove_title_heading: bool,
+    pub heading_levels: HeadingLevelMode,
+    pub image_cache_dir: Option<String>,
+}
+
+/// 默认配置, 调用方在此基础上修改
+pub fn default_config() -> Config {
+    importer_backend::Config::default().into()
+}
+
+impl From<importer_backend::Config> for Config {
+    fn from(config: importer_backend::Config) -> Self {
+        Self {
+            export_dir: config
+                .export_dir
+                .map(|dir| dir.to_string_lossy().to_string()),
+            colors: config.colors,
+            embed_providers: config.embed_providers,
+            min_duplicate_blocks: config.min_duplicate_blocks.map(|n| n as u64),
+            export_date: config.export_date.map(|date| date.to_string()),
+            date_mention_mark: config.date_mention_mark,
+            user_mention_format: config.user_mention_format,
+            tag_property: config.tag_property,
+            inline_tags: config.inline_tags,
+            created_property: config.created_property,
+            updated_property: config.updated_property,
+            timestamp_ids: config.timestamp_ids,
+            footnotes: config.footnotes,
+            table_of_contents: config.table_of_contents.into(),
+            breadcrumb: config.breadcrumb.into(),
+            child_page_links: config.child_page_links.into(),
+            remove_title_heading: config.remove_title_heading,
+            heading_levels: config.heading_levels.into(),
+            image_cache_dir: config
+                .image_cache_dir
+                .map(|dir| dir.to_string_lossy().to_string()),
+        }
+    }
+}
+
+impl Config {
+    pub(crate) fn into_backend(self) -> MyResult<importer_backend::Config> {
+        let export_date = self
+            .export_date
+            .map(|date| NaiveDate::parse_from_str(&date, "%Y-%m-%d"))
+            .transpose()
+            .map_err(anyhow::Error::from)?;
+        Ok(importer_backend::Config {
+            export_dir: self.export_dir.map(PathBuf::from),
+            colors: self.colors,
+            embed_providers: self.embed_providers,
+            min_duplicate_blocks: self.min_duplicate_blocks.map(|n| n as usize),
+            export_date,
+            date_mention_mark: self.date_mention_mark,
+            user_mention_format: self.user_mention_format,
+            tag_property: self.tag_property,
+            inline_tags: self.inline_tags,
+            created_property: self.created_property,
+            updated_property: self.updated_property,
+            timestamp_ids: self.timestamp_ids,
+            footnotes: self.footnotes,
+            table_of_contents: self.table_of_contents.into(),
+            breadcrumb: self.breadcrumb.into(),
+            child_page_links: self.child_page_links.into(),
+            remove_title_heading: self.remove_title_heading,
+            heading_levels: self.heading_levels.into(),
+            image_cache_dir: self.image_cache_dir.map(PathBuf::from),
+        })
+    }
+}
+
+impl From<importer_backend::NavBlockMode> for NavBlockMode {
+    fn from(mode: importer_backend::NavBlockMode) -> Self {
+        match mode {
+            importer_backend::NavBlockMode::Static => Self::Static,
+            importer_backend::NavBlockMode::Embed => Self::Embed,
+            importer_backend::NavBlockMode::Remove => Self::Remove,
+        }
+    }
+}
+
+impl From<NavBlockMode> for importer_backend::NavBlockMode {
+    fn from(mode: NavBlockMode) -> Self {
+        match mode {
+            NavBlockMode::Static => Self::Static,
+            NavBlockMode::Embed => Self::Embed,
+            NavBlockMode::Remove => Self::Remove,
+        }
+    }
+}
+
+impl From<importer_backend::ChildLinkMode> for ChildLinkMode {
+    fn from(mode: importer_backend::ChildLinkMode) -> Self {
+        match mode {
+            importer_backend::ChildLinkMode::Keep => Self::Keep,
+            importer_backend::ChildLinkMode::Remove => Self::Remove,
+            importer_backend::ChildLinkMode::Ref => Self::Ref,
+        }
+    }
+}
+
+impl From<ChildLinkMode> for importer_backend::ChildLinkMode {
+    fn from(mode: ChildLinkMode) -> Self {
+        match mode {
+            ChildLinkMode::Keep => Self::Keep,
+            ChildLinkMode::Remove => Self::Remove,
+            ChildLinkMode::Ref => Self::Ref,
+        }
+    }
+}
+
+impl From<importer_backend::HeadingLevelMode> for HeadingLevelMode {
+    fn from(mode: importer_backend::HeadingLevelMode) -> Self {
+        match mode {
+            importer_backend::HeadingLevelMode::Keep => Self::Keep,
+            importer_backend::HeadingLevelMode::Shift(levels) => Self::Shift { levels },
+            importer_backend::HeadingLevelMode::Compress => Self::Compress,
+        }
+    }
+}
+
+impl From<HeadingLevelMode> for importer_backend::HeadingLevelMode {
+    fn from(mode: HeadingLevelMode) -> Self {
+        match mode {
+            HeadingLevelMode::Keep => Self::Keep,
+            HeadingLevelMode::Shift { levels } => Self::Shift(levels),
+            HeadingLevelMode::Compress => Self::Compress,
+        }
+    }
+}
@@ -1,7 +1,9 @@
 uniffi::include_scaffolding!("lib");
 
+mod config;
 mod error;
 
+pub use config::{default_config, ChildLinkMode, Config, HeadingLevelMode, NavBlockMode};
 pub use error::MyError;
 use error::MyResult;
 use importer_backend::Notebook;
@@ -11,8 +13,8 @@ pub struct NotebookFfi {
 }
 
 impl NotebookFfi {
-    pub fn new(data_home: String, base_url: String) -> MyResult<Self> {
-        let notebook = Notebook::new(&data_home, &base_url)?;
+    pub fn new(base_url: String) -> MyResult<Self> {
+        let notebook = Notebook::new(&base_url)?;
         Ok(Self { core: notebook })
     }
 
@@ -26,6 +28,11 @@ impl NotebookFfi {
         Ok(())
     }
 
+    pub fn set_config(&self, config: Config) -> MyResult<()> {
+        self.core.set_config(config.into_backend()?)?;
+        Ok(())
+    }
+
     pub fn get_all_files(&self) -> MyResult<Vec<String>> {
         let files = self.core.get_all_files()?;
         Ok(files)
@@ -35,4 +42,19 @@ impl NotebookFfi {
         self.core.process_file(&path)?;
         Ok(())
     }
+
+    pub fn dedup_files(&self, paths: Vec<String>) -> MyResult<u64> {
+        let count = self.core.dedup_files(paths)?;
+        Ok(count as u64)
+    }
+
+    pub fn import_zip(&self, path: String) -> MyResult<Vec<String>> {
+        let ids = self.core.import_zip(&path)?;
+        Ok(ids)
+    }
+
+    pub fn import_json_dir(&self, path: String) -> MyResult<Vec<String>> {
+        let ids = self.core.import_json_dir(&path)?;
+        Ok(ids)
+    }
 }